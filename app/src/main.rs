@@ -1,8 +1,8 @@
-use std::{cell::{Cell, RefCell}, rc::Rc};
+use std::{cell::{Cell, RefCell}, path::Path, rc::Rc, time::Duration};
 use position::position::{DrawR, State};
 use adw::prelude::*;
-use gtk::{glib::{self, clone}, Align, ApplicationWindow, Box, Button, CheckButton, Entry, Label, Orientation, SpinButton, Stack, StackSwitcher, ToggleButton};
-use mainp::{Game, Machine, MachineErrors, SFResEx};
+use gtk::{glib::{self, clone}, Align, ApplicationWindow, Box, Button, CheckButton, Entry, Label, Orientation, ProgressBar, SpinButton, Stack, StackSwitcher, ToggleButton};
+use mainp::{AnalysisLine, EngineConfig, EngineMatch, EnginePlayer, Game, MachineErrors, NextError, SFResEx, SFScore};
 
 const APP_ID: &str = "org.gtk_rs.GObjectProperties3";
 const XDIRPIN: u8 = 16;
@@ -31,11 +31,12 @@ fn main() -> glib::ExitCode {
 
 
 fn build_ui(app: &adw::Application) {
-	let mut game = Rc::new(RefCell::new(Game {machine: Machine::dummy(), wm: false, bm: false, ws: false, bs: false, welo: 2000, belo: 2000, sftime: 1000, currentmove: None }));
+	let mut game = Rc::new(RefCell::new(Game::dummy()));
 	let wsbutton = CheckButton::with_label("   White moves with Stockfish -> Elo:");
 	let bsbutton = CheckButton::with_label("   Black moves with Stockfish -> Elo:");
 	let wmbutton = CheckButton::with_label("   White moves automatically");
 	let bmbutton = CheckButton::with_label("   Black moves automatically");
+	let ponderbutton = CheckButton::with_label("   Stockfish thinks in your time");
 	let welo = SpinButton::with_range(1320.0, 3190.0, 10.0);
 	let belo = SpinButton::with_range(1320.0, 3190.0, 10.0);
 	let sftime = SpinButton::with_range(100.0, 10000.0, 100.0);
@@ -43,6 +44,43 @@ fn build_ui(app: &adw::Application) {
 		.label("Start Game")
 		.build();
 	let savebutton = Button::with_label("Save Settings");
+	let enginepath = Entry::builder()
+		.placeholder_text("Path of the UCI engine, native for the built-in one")
+		.build();
+	let enginebutton = Button::with_label("Use engine");
+	let threads = SpinButton::with_range(1.0, 64.0, 1.0);
+	let hash = SpinButton::with_range(16.0, 4096.0, 16.0);
+	let skill = SpinButton::with_range(0.0, 20.0, 1.0);
+	let syzygypath = Entry::builder()
+		.placeholder_text("Syzygy tablebases")
+		.build();
+	let engineconfbutton = Button::with_label("Save engine options");
+	let matchpath = Entry::builder()
+		.placeholder_text("Second engine, empty for the same one")
+		.build();
+	let matchelo = SpinButton::with_range(1320.0, 3190.0, 10.0);
+	let matchgames = SpinButton::with_range(1.0, 100.0, 1.0);
+	let matchbutton = Button::with_label("Start match");
+	let matchlabel = Label::new(None);
+	let takebackbutton = Button::with_label("Take back");
+	let takebackplies = SpinButton::with_range(1.0, 20.0, 1.0);
+	let resignbutton = Button::with_label("Resign");
+	let offerdrawbutton = Button::with_label("Offer draw");
+	let acceptdrawbutton = Button::with_label("Accept draw");
+	let declinedrawbutton = Button::with_label("Decline draw");
+	let savepgnbutton = Button::with_label("Save game as PGN");
+	let chess960number = SpinButton::with_range(0.0, 959.0, 1.0);
+	chess960number.set_value(518.0);
+	let chess960button = Button::with_label("Start Chess960");
+	let analysebutton = Button::with_label("Analyse");
+	let analyselines = SpinButton::with_range(1.0, 5.0, 1.0);
+	let hintbutton = Button::with_label("Hint");
+	let evalbar = ProgressBar::builder()
+		.fraction(0.5)
+		.show_text(true)
+		.text("0.00")
+		.build();
+	let lineslabel = Label::new(None);
 	let moveentry = Entry::builder()
 		.placeholder_text("Enter your move:")
 		.secondary_icon_name("object-select-symbolic")
@@ -74,6 +112,12 @@ fn build_ui(app: &adw::Application) {
 		.spacing(6)
 		.orientation(Orientation::Horizontal)
 		.build();
+	let erowbox4 = Box::builder()
+		.valign(Align::Center)
+		.halign(Align::Center)
+		.spacing(6)
+		.orientation(Orientation::Horizontal)
+		.build();
 	let entera = Button::with_label("A");
 	let enterb = Button::with_label("B");
 	let enterc = Button::with_label("C");
@@ -90,6 +134,18 @@ fn build_ui(app: &adw::Application) {
 	let enter6 = Button::with_label("6");
 	let enter7 = Button::with_label("7");
 	let enter8 = Button::with_label("8");
+	let enterq = Button::with_label("=Q");
+	let enterr = Button::with_label("=R");
+	let enterbp = Button::with_label("=B");
+	let entern = Button::with_label("=N");
+	let enterpk = Button::with_label("K");
+	let enterpq = Button::with_label("Q");
+	let enterpr = Button::with_label("R");
+	let enterpb = Button::with_label("B");
+	let enterpn = Button::with_label("N");
+	let enterx = Button::with_label("x");
+	let entershort = Button::with_label("O-O");
+	let enterlong = Button::with_label("O-O-O");
 	let backspace = Button::with_label("    <=    ");
 	let _enterleft = Button::with_label("    <    ");
 	let _enterright = Button::with_label("    >    ");
@@ -135,12 +191,21 @@ fn build_ui(app: &adw::Application) {
 	let statuslabel = Label::new(Some("OK"));
 	sftimebox.append(&sftime);
 	sftimebox.append(&Label::new(Some("  Time for Stockfish to think")));
+	let enginebox = Box::builder()
+		.valign(Align::Center)
+		.halign(Align::Start)
+		.spacing(12)
+		.orientation(Orientation::Horizontal)
+		.build();
+	enginebox.append(&enginepath);
+	enginebox.append(&enginebutton);
 	welobox.append(&wsbutton);
 	welobox.append(&welo);
 	belobox.append(&bsbutton);
 	belobox.append(&belo);
 	sfbox.append(&wmbutton);
 	sfbox.append(&bmbutton);
+	sfbox.append(&ponderbutton);
 	savebox.append(&sfbox);
 	savebox.append(&savebutton);
 	erowbox1.append(&entera);
@@ -159,10 +224,23 @@ fn build_ui(app: &adw::Application) {
 	erowbox2.append(&enter6);
 	erowbox2.append(&enter7);
 	erowbox2.append(&enter8);
+	erowbox3.append(&enterq);
+	erowbox3.append(&enterr);
+	erowbox3.append(&enterbp);
+	erowbox3.append(&entern);
 	//erowbox3.append(&enterleft);
 	//erowbox3.append(&enterright);
 	erowbox3.append(&backspace);
 	//erowbox3.append(&makemove);
+	erowbox4.append(&enterpk);
+	erowbox4.append(&enterpq);
+	erowbox4.append(&enterpr);
+	erowbox4.append(&enterpb);
+	erowbox4.append(&enterpn);
+	erowbox4.append(&enterx);
+	erowbox4.append(&entershort);
+	erowbox4.append(&enterlong);
+	typingbox.append(&erowbox4);
 	typingbox.append(&erowbox1);
 	typingbox.append(&erowbox2);
 	typingbox.append(&erowbox3);
@@ -225,12 +303,92 @@ fn build_ui(app: &adw::Application) {
 	setupbox.append(&belobox);
 	setupbox.append(&savebox);
 	setupbox.append(&sftimebox);
+	setupbox.append(&enginebox);
+	let engineconfbox = Box::builder()
+		.valign(Align::Center)
+		.halign(Align::Start)
+		.spacing(12)
+		.orientation(Orientation::Horizontal)
+		.build();
+	engineconfbox.append(&Label::new(Some("Threads")));
+	engineconfbox.append(&threads);
+	engineconfbox.append(&Label::new(Some("Hash MB")));
+	engineconfbox.append(&hash);
+	engineconfbox.append(&Label::new(Some("Skill")));
+	engineconfbox.append(&skill);
+	engineconfbox.append(&syzygypath);
+	engineconfbox.append(&engineconfbutton);
+	setupbox.append(&engineconfbox);
 	//setupbox.append(&Frame::builder().child(&statuslabel).margin_top(12).build());
 
 	moveenterbox.append(&typingbox);
 	moveenterbox.append(&moveentry);
 
+	let takebackbox = Box::builder()
+		.valign(Align::Center)
+		.halign(Align::Center)
+		.spacing(12)
+		.orientation(Orientation::Horizontal)
+		.build();
+	takebackbox.append(&takebackplies);
+	takebackbox.append(&Label::new(Some("half moves")));
+	takebackbox.append(&takebackbutton);
+
+	let resultbox = Box::builder()
+		.valign(Align::Center)
+		.halign(Align::Center)
+		.spacing(12)
+		.orientation(Orientation::Horizontal)
+		.build();
+	resultbox.append(&resignbutton);
+	resultbox.append(&offerdrawbutton);
+	resultbox.append(&acceptdrawbutton);
+	resultbox.append(&declinedrawbutton);
+
 	actionsbox.append(&startbutton);
+	actionsbox.append(&takebackbox);
+	actionsbox.append(&resultbox);
+	actionsbox.append(&savepgnbutton);
+
+	let matchbox = Box::builder()
+		.valign(Align::Center)
+		.halign(Align::Center)
+		.spacing(12)
+		.orientation(Orientation::Horizontal)
+		.build();
+	matchbox.append(&matchpath);
+	matchbox.append(&Label::new(Some("Elo")));
+	matchbox.append(&matchelo);
+	matchbox.append(&Label::new(Some("Games")));
+	matchbox.append(&matchgames);
+	matchbox.append(&matchbutton);
+	actionsbox.append(&matchbox);
+	actionsbox.append(&matchlabel);
+
+	let chess960box = Box::builder()
+		.valign(Align::Center)
+		.halign(Align::Center)
+		.spacing(12)
+		.orientation(Orientation::Horizontal)
+		.build();
+	chess960box.append(&Label::new(Some("Position")));
+	chess960box.append(&chess960number);
+	chess960box.append(&chess960button);
+	actionsbox.append(&chess960box);
+
+	let analysebox = Box::builder()
+		.valign(Align::Center)
+		.halign(Align::Center)
+		.spacing(12)
+		.orientation(Orientation::Horizontal)
+		.build();
+	analysebox.append(&analyselines);
+	analysebox.append(&Label::new(Some("lines")));
+	analysebox.append(&analysebutton);
+	analysebox.append(&hintbutton);
+	actionsbox.append(&analysebox);
+	actionsbox.append(&evalbar);
+	actionsbox.append(&lineslabel);
 
 	mainbox.append(&stackswitcher);
 	mainbox.append(&stack);
@@ -270,11 +428,173 @@ fn build_ui(app: &adw::Application) {
 		} else {
 			game.borrow_mut().set_settings((wmbutton.is_active(), bmbutton.is_active(), wsbutton.is_active(), 
 			bsbutton.is_active(), welo.value_as_int().abs() as u32, belo.value_as_int().abs() as u32, sftime.value_as_int().abs() as u32));
+			game.borrow_mut().ponder = ponderbutton.is_active();
 		};
 	}));
 
-	let running = Cell::new(false);
-	startbutton.connect_clicked(move |but| {
+	enginepath.set_text(&game.borrow().engine_path);
+	enginebutton.connect_clicked(clone!(#[strong]game, #[strong]statuslabel, move |_| {
+		let path = enginepath.text().to_string();
+		match game.borrow_mut().set_engine_path(&path) {
+			Ok(name) => statuslabel.set_text(&format!("Using {}", name)),
+			Err(rr) => statuslabel.set_text(&format!("Failed to start engine {}: {:?}", path, rr))
+		};
+		}));
+
+	{
+		let conf = &game.borrow().engine_config;
+		threads.set_value(conf.threads.unwrap_or(1) as f64);
+		hash.set_value(conf.hash.unwrap_or(16) as f64);
+		skill.set_value(conf.skill_level.unwrap_or(20) as f64);
+		syzygypath.set_text(conf.syzygy_path.as_deref().unwrap_or(""));
+	}
+	engineconfbutton.connect_clicked(clone!(#[strong]game, #[strong]statuslabel, move |_| {
+		let path = syzygypath.text().to_string();
+		let conf = EngineConfig {
+			path: None,
+			threads: Some(threads.value_as_int() as i64),
+			hash: Some(hash.value_as_int() as i64),
+			syzygy_path: if path.is_empty() {None} else {Some(path)},
+			skill_level: Some(skill.value_as_int() as i64)
+		};
+		let mut gm = game.borrow_mut();
+		match gm.set_engine_config(conf).map_err(|rr| format!("{:?}", rr)).and_then(|_| gm.save_engine_config().map_err(|rr| format!("{:?}", rr))) {
+			Ok(path) => statuslabel.set_text(&format!("Engine options saved to {}", path.display())),
+			Err(rr) => statuslabel.set_text(&format!("Failed to set engine options: {}", rr))
+		};
+		}));
+
+	takebackbutton.connect_clicked(clone!(#[strong]game, #[strong]statuslabel, move |_| {
+		let plies = takebackplies.value_as_int().unsigned_abs() as usize;
+		match game.borrow_mut().takeback(plies) {
+			Ok(_) => statuslabel.set_text(&format!("Took back {} half moves", plies)),
+			Err(rr) => statuslabel.set_text(&format!("Failed to take back moves: {:?}", rr))
+		};
+		}));
+
+	resignbutton.connect_clicked(clone!(#[strong]game, #[strong]statuslabel, move |_| {
+		let mut gm = game.borrow_mut();
+		let color = human_color(&gm);
+		match gm.resign(color) {
+			Ok(st) => show_result(&statuslabel, st),
+			Err(rr) => statuslabel.set_text(&format!("Failed to resign: {:?}", rr))
+		};
+		}));
+
+	offerdrawbutton.connect_clicked(clone!(#[strong]game, #[strong]statuslabel, move |_| {
+		let mut gm = game.borrow_mut();
+		let color = human_color(&gm);
+		match gm.offer_draw(color) {
+			Ok(State::Normal) if gm.draw_offer.is_some() => statuslabel.set_text("Draw offered"),
+			Ok(State::Normal) => statuslabel.set_text("Stockfish declines the draw"),
+			Ok(st) => show_result(&statuslabel, st),
+			Err(rr) => statuslabel.set_text(&format!("Failed to offer draw: {:?}", rr))
+		};
+		}));
+
+	acceptdrawbutton.connect_clicked(clone!(#[strong]game, #[strong]statuslabel, move |_| {
+		match game.borrow_mut().accept_draw() {
+			Ok(st) => show_result(&statuslabel, st),
+			Err(rr) => statuslabel.set_text(&format!("Failed to accept draw: {:?}", rr))
+		};
+		}));
+
+	declinedrawbutton.connect_clicked(clone!(#[strong]game, #[strong]statuslabel, move |_| {
+		match game.borrow_mut().decline_draw() {
+			Ok(_) => statuslabel.set_text("Draw declined"),
+			Err(rr) => statuslabel.set_text(&format!("Failed to decline draw: {:?}", rr))
+		};
+		}));
+
+	savepgnbutton.connect_clicked(clone!(#[strong]game, #[strong]statuslabel, move |_| {
+		match game.borrow().save_pgn() {
+			Ok(path) => statuslabel.set_text(&format!("Game saved to {}", path)),
+			Err(rr) => statuslabel.set_text(&format!("Failed to save game: {:?}", rr))
+		};
+		}));
+
+	chess960button.connect_clicked(clone!(#[strong]game, #[strong]statuslabel, move |_| {
+		let n = chess960number.value_as_int().unsigned_abs() as u16;
+		match game.borrow_mut().start_chess960(n) {
+			Ok(_) => statuslabel.set_text(&format!("Chess960 position {} set up", n)),
+			Err(rr) => statuslabel.set_text(&format!("Failed to set up position: {:?}", rr))
+		};
+		}));
+
+	analysebutton.connect_clicked(clone!(#[strong]game, #[strong]statuslabel, move |_| {
+		let lines = analyselines.value_as_int().unsigned_abs();
+		let mut gm = game.borrow_mut();
+		let white = gm.get_current_color();
+		match gm.analyse(lines) {
+			Ok(res) => {
+				if let Some(best) = res.first() {
+					evalbar.set_fraction(white_share(best.score, white));
+					evalbar.set_text(Some(&score_text(best.score, white)));
+				};
+				lineslabel.set_text(&res.iter().map(|l| line_text(l, white)).collect::<Vec<String>>().join("\n"));
+				statuslabel.set_text("Analysis finished");
+			},
+			Err(rr) => statuslabel.set_text(&format!("Failed to analyse: {:?}", rr))
+		};
+		}));
+
+	hintbutton.connect_clicked(clone!(#[strong]game, #[strong]statuslabel, move |_| {
+		match game.borrow_mut().hint() {
+			Ok(Some(mv)) => statuslabel.set_text(&format!("Hint: {}", mv)),
+			Ok(None) => statuslabel.set_text("No move possible"),
+			Err(rr) => statuslabel.set_text(&format!("Failed to get hint: {:?}", rr))
+		};
+		}));
+
+	let running = Rc::new(Cell::new(false));
+	matchelo.set_value(1500.0);
+	matchgames.set_value(2.0);
+	matchbutton.connect_clicked(clone!(#[strong]game, #[strong]statuslabel, #[strong]running, #[strong]startbutton, move |_| {
+		let players = {
+			let gm = game.borrow();
+			let second = matchpath.text().to_string();
+			let path = if second.is_empty() {gm.engine_path.clone()} else {second};
+			[engine_player(&gm.engine_path, gm.welo, &gm.engine_config, gm.sftime),
+				engine_player(&path, matchelo.value_as_int() as u32, &gm.engine_config, gm.sftime)]
+		};
+		let games = matchgames.value_as_int().unsigned_abs();
+		match game.borrow_mut().start_match(EngineMatch::new(players, games)) {
+			Ok(_) => statuslabel.set_text("Match started"),
+			Err(rr) => return statuslabel.set_text(&format!("Failed to start match: {:?}", rr))
+		};
+		running.set(true);
+		startbutton.set_label("Pause");
+		glib::timeout_add_local(Duration::from_millis(100), clone!(#[strong]game, #[strong]statuslabel, #[strong]matchlabel, #[strong]running, move || {
+			if !running.get() {
+				return glib::ControlFlow::Continue
+			};
+			let mut gm = game.borrow_mut();
+			let res = gm.next();
+			if let Some(m) = &gm.engine_match {
+				matchlabel.set_text(&m.standings());
+			};
+			match res {
+				Ok(State::Normal) => {
+					statuslabel.set_text(&format!("{} played", gm.history.last().map(|(_, mv)| mv.as_str()).unwrap_or("")));
+					glib::ControlFlow::Continue
+				},
+				Ok(st) => {
+					show_result(&statuslabel, st);
+					glib::ControlFlow::Continue
+				},
+				Err(NextError::GameOver) => {
+					statuslabel.set_text("Match finished");
+					glib::ControlFlow::Break
+				},
+				Err(rr) => {
+					statuslabel.set_text(&format!("Match stopped: {:?}", rr));
+					glib::ControlFlow::Break
+				}
+			}
+			}));
+		}));
+
+	startbutton.connect_clicked(clone!(#[strong]running, move |but| {
 		running.set(!running.get());
 		if running.get() {
 			but.set_label("Pause");
@@ -282,7 +602,7 @@ fn build_ui(app: &adw::Application) {
 			but.set_label("Resume");
 		}
 		println!("{:?}", running)
-		});
+		}));
 // region button inputs
 	enter1.connect_clicked(clone!(#[strong]moveentry, move |_| {
 		let mut t = moveentry.text().to_string();
@@ -364,6 +684,62 @@ fn build_ui(app: &adw::Application) {
 		t.push('h');
 		moveentry.set_text(&t);
 		}));
+	enterq.connect_clicked(clone!(#[strong]moveentry, move |_| {
+		let mut t = moveentry.text().to_string();
+		t.push('q');
+		moveentry.set_text(&t);
+		}));
+	enterr.connect_clicked(clone!(#[strong]moveentry, move |_| {
+		let mut t = moveentry.text().to_string();
+		t.push('r');
+		moveentry.set_text(&t);
+		}));
+	enterbp.connect_clicked(clone!(#[strong]moveentry, move |_| {
+		let mut t = moveentry.text().to_string();
+		t.push('b');
+		moveentry.set_text(&t);
+		}));
+	entern.connect_clicked(clone!(#[strong]moveentry, move |_| {
+		let mut t = moveentry.text().to_string();
+		t.push('n');
+		moveentry.set_text(&t);
+		}));
+	enterpk.connect_clicked(clone!(#[strong]moveentry, move |_| {
+		let mut t = moveentry.text().to_string();
+		t.push('K');
+		moveentry.set_text(&t);
+		}));
+	enterpq.connect_clicked(clone!(#[strong]moveentry, move |_| {
+		let mut t = moveentry.text().to_string();
+		t.push('Q');
+		moveentry.set_text(&t);
+		}));
+	enterpr.connect_clicked(clone!(#[strong]moveentry, move |_| {
+		let mut t = moveentry.text().to_string();
+		t.push('R');
+		moveentry.set_text(&t);
+		}));
+	enterpb.connect_clicked(clone!(#[strong]moveentry, move |_| {
+		let mut t = moveentry.text().to_string();
+		t.push('B');
+		moveentry.set_text(&t);
+		}));
+	enterpn.connect_clicked(clone!(#[strong]moveentry, move |_| {
+		let mut t = moveentry.text().to_string();
+		t.push('N');
+		moveentry.set_text(&t);
+		}));
+	enterx.connect_clicked(clone!(#[strong]moveentry, move |_| {
+		let mut t = moveentry.text().to_string();
+		t.push('x');
+		moveentry.set_text(&t);
+		}));
+	entershort.connect_clicked(clone!(#[strong]moveentry, move |_| {
+		moveentry.set_text("O-O");
+		}));
+	enterlong.connect_clicked(clone!(#[strong]moveentry, move |_| {
+		moveentry.set_text("O-O-O");
+		}));
 	backspace.connect_clicked(clone!(#[strong]moveentry, move |_| {
 		let mut t = moveentry.text().to_string();
 		t.pop();
@@ -379,14 +755,31 @@ fn get_game() -> Result<Game, MachineErrors> {
 	Game::new((true, XDIRPIN, XSTEPPIN, XENBPIN), (false, YDIRPIN, YSTEPPIN, YENBPIN), MAGNETPIN)
 }
 
-fn loop_moves(gm: &mut Game, ent: &Entry, statuslabel: &Label) {
-	todo!()
+fn loop_moves(gm: &mut Game, ent: &Entry, statuslabel: &Label) {	// the move of the human, then sf answers until a human is to move again
+	if !gm.is_sf(gm.get_current_color()) {
+		handmove(gm, ent, statuslabel);
+	};
+	while gm.result == State::Normal && gm.is_sf(gm.get_current_color()) {
+		let plies = gm.history.len();
+		sfmove(gm, ent, statuslabel);
+		if gm.history.len() == plies {	// sf failed or resigned, the status label tells why
+			break
+		};
+	};
+}
+
+fn engine_player(path: &str, elo: u32, config: &EngineConfig, time: u32) -> EnginePlayer {	// named after the file of the engine and its strength
+	let file = Path::new(path).file_name().map(|f| f.to_string_lossy().to_string()).unwrap_or(path.to_string());
+	EnginePlayer { name: format!("{} {}", file, elo), path: path.to_string(), elo: Some(elo), config: config.clone(), time }
 }
 
 fn handmove(gm: &mut Game, ent: &Entry, statuslabel: &Label) {
 	let text = ent.text().to_string().trim().to_string();
-	if text.len() > 3 {
-		movcore(gm, ent, statuslabel, &text);
+	if text.len() > 1 {
+		match gm.parse_move(&text) {
+			Ok(movtext) => movcore(gm, ent, statuslabel, &movtext),
+			Err(rr) => statuslabel.set_text(&format!("Invalid move {}: {:?}", text, rr))
+		};
 		//let res = pos.position.update(, &text, if pos.position.colorw {welo.value_as_int() as u32} else {belo.value_as_int() as u32}, sftime.value_as_int() as u32)
 	} else {
 		statuslabel.set_text("Move too short");
@@ -396,6 +789,11 @@ fn handmove(gm: &mut Game, ent: &Entry, statuslabel: &Label) {
 fn sfmove(gm: &mut Game, ent: &Entry, statuslabel: &Label) {
 	match (gm.get_current_color(), gm.ws, gm.bs) {
 		(true,  true, _) | (false, _, true) => {
+			match gm.sf_resigns() {
+				Ok(State::Normal) => {},
+				Ok(st) => return show_result(statuslabel, st),
+				Err(rr) => return statuslabel.set_text(&format!("Failed to get Stockfish eval: {:?}", rr))
+			};
 			match gm.get_sf_move() {
 				Ok(mv) => {
 					match mv {
@@ -416,10 +814,11 @@ fn sfmove(gm: &mut Game, ent: &Entry, statuslabel: &Label) {
 }
 
 fn movcore(gm: &mut Game, ent: &Entry, statuslabel: &Label, movtext: &str) {
+	let san = gm.to_san(movtext).unwrap_or(movtext.to_string());
 	match gm.check_possible_move(&movtext) {
-		Ok(ty) => {
-			statuslabel.set_text(&format!("Move valid: {:?}", ty));
-			match gm.update(Game::ctim(&movtext).unwrap(), &movtext, 3000, 1000) {
+		Ok(_) => {
+			statuslabel.set_text(&format!("Move valid: {}", san));
+			match gm.update(Game::ctim(&movtext).unwrap(), &movtext) {
 				Ok((st, pfi, oldpos)) => {
 					println!("there");
 					println!("{:?}, {:?}", gm.wm, gm.bm);
@@ -428,39 +827,83 @@ fn movcore(gm: &mut Game, ent: &Entry, statuslabel: &Label, movtext: &str) {
 							//let currentpos = &gm.machine.pos_mtr;
 							statuslabel.set_text("Now moving automatically");
 							match gm.execute_move(pfi, oldpos) {
-								Ok(_) => statuslabel.set_text("White moved automatically and successfully"),
-								Err(rr) => statuslabel.set_text(&format!("Failed to make automatic white move: {:?}", rr))
+								Ok(_) => statuslabel.set_text(&format!("White moved {} automatically and successfully", san)),
+								Err(rr) => statuslabel.set_text(&format!("Failed to make automatic white move {}: {:?}", san, rr))
 							}
 						}
 					} else {
 						if gm.bm {
 							statuslabel.set_text("Now moving automatically");
 							match gm.execute_move(pfi, oldpos) {
-								Ok(_) => statuslabel.set_text("Black moved automatically and successfully"),
-								Err(rr) => statuslabel.set_text(&format!("Failed to make automatic black move: {:?}", rr))
+								Ok(_) => statuslabel.set_text(&format!("Black moved {} automatically and successfully", san)),
+								Err(rr) => statuslabel.set_text(&format!("Failed to make automatic black move {}: {:?}", san, rr))
 							}
 						}
 					}
 					ent.set_text("");
 					match st {
-						State::Mate(true) => {
-							statuslabel.set_text("Checkmate! White wins!");
-						},
-						State::Mate(false) => statuslabel.set_text("Checkmate! Black wins!"),
-						State::Draw(DrawR::FiftyMove) => statuslabel.set_text("Draw by fifty-move rule"),
-						State::Draw(DrawR::Repetition) => statuslabel.set_text("Draw by repetition"),
-						State::Draw(DrawR::Stalemate) => statuslabel.set_text("Draw by stalemate"),
-						State::Normal => statuslabel.set_text("Move made successfully"),
-						_ => {}
+						State::Normal => statuslabel.set_text(&format!("{} played", san)),
+						_ => statuslabel.set_text(&format!("{}  {}", san, result_text(st)))
 					};
 					gm.machine.print_status();
 				},
-				Err(rr) => {statuslabel.set_text(&format!("Failed to update position: {:?}", rr).as_str());
+				Err(rr) => {statuslabel.set_text(&format!("Failed to update position with {}: {:?}", san, rr).as_str());
 					println!("fail")}
 			}},
 		Err(rr) => {//makemove.set_sensitive(false);
-			statuslabel.set_text(&format!("Invalid move: {:?}", rr))}
+			statuslabel.set_text(&format!("Invalid move {}: {:?}", san, rr))}
 	};
 }
 
+fn show_result(statuslabel: &Label, st: State) {
+	statuslabel.set_text(result_text(st));
+}
+
+fn result_text(st: State) -> &'static str {
+	match st {
+		State::Mate(true) => "Checkmate! White wins!",
+		State::Mate(false) => "Checkmate! Black wins!",
+		State::Draw(DrawR::FiftyMove) => "Draw by fifty-move rule",
+		State::Draw(DrawR::Repetition) => "Draw by repetition",
+		State::Draw(DrawR::FivefoldRepetition) => "Draw by fivefold repetition",
+		State::Draw(DrawR::Stalemate) => "Draw by stalemate",
+		State::Draw(DrawR::InsufficientMaterial) => "Draw by insufficient material",
+		State::Draw(DrawR::Agreement) => "Draw by agreement",
+		State::Draw(DrawR::Adjudication) => "Draw by adjudication",
+		State::Resign(true) => "Black resigns! White wins!",
+		State::Resign(false) => "White resigns! Black wins!",
+		State::Normal => "Move made successfully"
+	}
+}
+
+fn white_share(score: SFScore, white_to_move: bool) -> f64 {	// part of the eval bar which belongs to white, win probability like lichess
+	match score {
+		SFScore::Centipawns(cp) => 1.0 / (1.0 + 10f64.powf(-cp as f64 / 400.0)),
+		SFScore::Mate(0) if white_to_move => 0.0,	// mate on the board, the side to move has lost, whichever view the score has
+		SFScore::Mate(0) => 1.0,
+		SFScore::Mate(n) if n > 0 => 1.0,
+		SFScore::Mate(_) => 0.0
+	}
+}
+
+fn score_text(score: SFScore, white_to_move: bool) -> String {	// e.g. +0.35 or #-3, the result if the side to move is mated
+	match score {
+		SFScore::Centipawns(cp) => format!("{:+.2}", cp as f64 / 100.0),
+		SFScore::Mate(0) => String::from(if white_to_move {"0-1"} else {"1-0"}),
+		SFScore::Mate(n) => format!("#{}", n)
+	}
+}
+
+fn line_text(line: &AnalysisLine, white_to_move: bool) -> String {	// e.g. "+0.35 (d18) e4 e5 Nf3"
+	format!("{} (d{}) {}", score_text(line.score, white_to_move), line.info.depth.unwrap_or(0), line.san.join(" "))
+}
+
+fn human_color(gm: &Game) -> bool {	// the side at the board, the side to move if both or neither are played by sf
+	match (gm.ws, gm.bs) {
+		(true, false) => false,
+		(false, true) => true,
+		_ => gm.get_current_color()
+	}
+}
+
 //(wmbutton.is_active(), bmbutton.is_active(), wsbutton.is_active(), bsbutton.is_active(), welo.value_as_int().abs() as u32, belo.value_as_int().abs() as u32, sftime.value_as_int().abs() as u32)
\ No newline at end of file
@@ -1,12 +1,21 @@
 use mctrl::{delay::delaymics, motor::{rps_to_del, Magnet, MotorInstructions, MotorMoveType, Mtr, MtrErrors, PosNow, Speeds}};
-use position::position::{ctim, MoveError, MoveType, PFError, PFIType, Position, State, UpdateError};
-use stockfish::{SFErrors, SFResults};
+use position::pgn::{self, PgnError};
+use position::position::{ctim, DrawR, FenError, MoveError, MoveType, PFError, PFIType, Position, SetupError, State, UpdateError};
+use stockfish::{ConfigError, Engine, OptionValue, SearchInfo, SearchLimits, SFErrors, SFResults};
+pub use stockfish::{EngineConfig, SFScore};
 pub use stockfish::SFResults as SFResEx;
+use native::NativeEngine;
+use std::{env, fs, io::{ErrorKind, Write}, path::PathBuf, time::{Instant, SystemTime, UNIX_EPOCH}};
+
+const PGN_DIR: &str = "chess_firmware/games";   // finished games are saved here, in the data directory of the user, see pgn_dir()
 
 #[derive(Debug)]
 pub enum MachineErrors {
-    Position(MoveError),
-    Motor(MtrErrors)
+    Fen(FenError),
+    Motor(MtrErrors),
+    Setup(SetupError),
+    Pathfinding(PFError),
+    Chess960(u16)   // number of the chess960 starting position is not in 0..960
 }
 #[derive(Debug)]
 pub struct Machine {    // contains and manages all components, keeps track of position
@@ -39,11 +48,32 @@ impl Machine {
         Ok(Self { xmtr, ymtr, magnet: mgnt, position: Position::new_reset(), pos_mtr: PosNow::new() })
     }
 
-    pub fn set_position(&mut self, fen: &str) -> Result<(), MachineErrors>{ // sets position to a given 
-        self.position = match Position::from_fen(fen) {
+    pub fn set_position(&mut self, fen: &str) -> Result<(), MachineErrors>{ // sets position to a given fen and moves the pieces on the board accordingly
+        let target = match Position::from_fen(fen) {
+            Ok(res) => res,
+            Err(rr) => return Err(MachineErrors::Fen(rr))
+        };
+        self.arrange(&target)
+    }
+
+    pub fn arrange(&mut self, target: &Position) -> Result<(), MachineErrors> {  // moves the pieces on the board to the given position, sidebars are used as needed
+        let (mov, newpos) = match self.position.setup_moves(target) {
             Ok(res) => res,
-            Err(rr) => return Err(MachineErrors::Position(rr))
+            Err(rr) => return Err(MachineErrors::Setup(rr))
         };
+        match self.execute(&mov, self.position.clone()) {
+            Ok(_) => {},
+            Err(rr) => return Err(MachineErrors::Pathfinding(rr))
+        };
+        self.position = newpos;
+        Ok(())
+    }
+
+    pub fn execute(&mut self, mov: &Vec<PFIType>, mut oldpos: Position) -> Result<(), PFError> { // calculates the motor instructions for the given moves and performs them
+        let mi = oldpos.pathfinding(mov, &mut self.pos_mtr)?;
+        println!("position now: {:?}", self.pos_mtr);
+        mi.print_out();
+        self.do_mi(mi);
         Ok(())
     }
 
@@ -132,7 +162,206 @@ pub struct Game {
     pub welo: u32,
     pub belo:  u32,
     pub sftime: u32,
-    pub currentmove: Option<String>
+    pub currentmove: Option<String>,
+    pub history: Vec<(Position, String)>,   // position before each move and the move itself
+    pub hash_history: Vec<u64>, // repetition keys of all positions of the game, including the current one
+    pub result: State,  // Normal while the game is running
+    pub draw_offer: Option<bool>,   // color of the side that offered a draw, true for white
+    pub resign_threshold: i32,  // sf resigns when its eval drops below minus this many centipawns
+    pub draw_margin: i32,   // sf accepts a draw offer when its eval is at most this many centipawns
+    pub pgn_dir: Option<String>, // finished games are saved there as pgn, None disables saving
+    pub engine_path: String,    // uci engine which plays and evaluates, see stockfish::find_engine()
+    pub engine_config: EngineConfig,    // options which are set whenever the engine starts
+    engine: Option<Engine>, // engine process, started when it is needed first and kept for the whole game
+    pub clock: Option<Clock>,   // None plays without clock, sf thinks sftime per move then
+    pub ponder: bool,   // sf thinks on the expected reply while the human is to move
+    ponder_move: Option<String>,    // reply sf expects, from its last search
+    pending: Option<String>,    // fen of the position the running search is for, also while pondering
+    sf_score: Option<(bool, SFScore)>,  // score of the last search of sf and the side it played
+    scores: Vec<Option<SFScore>>,   // score from the view of white for every half move played by next(), for adjudication
+    pub engine_match: Option<EngineMatch>,  // engine against engine, see start_match()
+    match_engines: [Option<Engine>; 2],  // engines of the match players, started on first use
+    user_settings: Option<(bool, bool, bool, bool)>    // ws, bs, wm and bm before the match
+}
+
+#[derive(Debug)]
+#[derive(Clone, PartialEq, Eq)]
+pub struct EnginePlayer {   // one side of an engine match
+    pub name: String,   // for the pgn and the standings
+    pub path: String,
+    pub elo: Option<u32>,   // None for full strength
+    pub config: EngineConfig,   // e.g. Skill Level or Threads
+    pub time: u32   // milliseconds per move if the game has no clock
+}
+
+#[derive(Debug)]
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub struct Adjudication {   // rules which end engine games early, the scores are those of the moves played
+    pub resign_score: i32,  // a side loses if it is this many centipawns behind ...
+    pub resign_plies: usize,    // ... for this many half moves in a row, 0 disables it
+    pub draw_score: i32,    // a draw if the score stays within this many centipawns ...
+    pub draw_plies: usize,  // ... for this many half moves in a row, 0 disables it ...
+    pub draw_from: usize,   // ... but not before this half move
+    pub max_plies: usize    // draw after this many half moves, 0 for no limit
+}
+
+impl Default for Adjudication {
+    fn default() -> Self {
+        Adjudication { resign_score: 1000, resign_plies: 6, draw_score: 10, draw_plies: 16, draw_from: 80, max_plies: 400 }
+    }
+}
+
+impl Adjudication {
+    pub fn judge(&self, scores: &[Option<SFScore>]) -> Option<State> {  // result for the scores of all half moves so far from the view of white, None if the game goes on
+        let cp = |s: &Option<SFScore>| match s {
+            Some(SFScore::Centipawns(cp)) => Some(*cp),
+            Some(SFScore::Mate(n)) => Some(if *n > 0 {100000} else {-100000}),
+            None => None
+        };
+        let last = |n: usize| if n > 0 && scores.len() >= n {Some(scores[scores.len() - n..].iter().map(cp))} else {None};
+        if let Some(mut l) = last(self.resign_plies) {
+            if l.clone().all(|c| c.is_some_and(|c| c >= self.resign_score)) {
+                return Some(State::Resign(true))
+            };
+            if l.all(|c| c.is_some_and(|c| c <= -self.resign_score)) {
+                return Some(State::Resign(false))
+            };
+        };
+        if let Some(mut l) = last(self.draw_plies) && scores.len() >= self.draw_from && l.all(|c| c.is_some_and(|c| c.abs() <= self.draw_score)) {
+            return Some(State::Draw(DrawR::Adjudication))
+        };
+        if self.max_plies > 0 && scores.len() >= self.max_plies {
+            return Some(State::Draw(DrawR::Adjudication))
+        };
+        None
+    }
+}
+
+#[derive(Debug)]
+#[derive(Clone, PartialEq, Eq)]
+pub struct EngineMatch {    // series of engine games, the players change colors after every game
+    pub players: [EnginePlayer; 2],
+    pub games: u32,
+    pub fen: Option<String>,    // start position of every game, None for the usual one
+    pub adjudication: Adjudication,
+    pub wins: [u32; 2],
+    pub draws: u32
+}
+
+impl EngineMatch {
+    pub fn new(players: [EnginePlayer; 2], games: u32) -> Self {
+        EngineMatch { players, games, fen: None, adjudication: Adjudication::default(), wins: [0, 0], draws: 0 }
+    }
+
+    pub fn played(&self) -> u32 {
+        self.wins[0] + self.wins[1] + self.draws
+    }
+
+    pub fn finished(&self) -> bool {
+        self.played() >= self.games
+    }
+
+    pub fn white(&self) -> usize {  // player with white in the running game, the first one starts
+        (self.played() % 2) as usize
+    }
+
+    pub fn points(&self, player: usize) -> f32 {
+        self.wins[player] as f32 + self.draws as f32 / 2.0
+    }
+
+    pub fn standings(&self) -> String { // e.g. "Stockfish 1.5 - 0.5 Komodo (2 of 4 games)"
+        format!("{} {} - {} {} ({} of {} games)", self.players[0].name, self.points(0), self.points(1), self.players[1].name, self.played(), self.games)
+    }
+
+    fn record(&mut self, state: State) {   // result of the running game
+        let white = self.white();
+        match state {
+            State::Mate(w) | State::Resign(w) => self.wins[if w {white} else {1 - white}] += 1,
+            State::Draw(_) => self.draws += 1,
+            State::Normal => {}
+        };
+    }
+}
+
+#[derive(Debug)]
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub struct Clock {  // chess clock with increment, all times in milliseconds
+    pub time: u32,  // time control: starting time and increment per move
+    pub inc: u32,
+    pub wtime: u32, // remaining times when the current move started
+    pub btime: u32,
+    running: Option<Instant>    // start of the current move, None while the clock is stopped
+}
+
+impl Clock {
+    pub fn new(time: u32, inc: u32) -> Self {   // stopped clock, it starts with the game
+        Clock { time, inc, wtime: time, btime: time, running: None }
+    }
+
+    pub fn remaining(&self, color: bool, to_move: bool) -> u32 { // time left for the given side, the running move is included
+        let elapsed = match self.running {
+            Some(start) if color == to_move => start.elapsed().as_millis().min(u32::MAX as u128) as u32,
+            _ => 0
+        };
+        (if color {self.wtime} else {self.btime}).saturating_sub(elapsed)
+    }
+
+    fn press(&mut self, color: bool) {  // the given side finished its move, it gets the increment and the clock runs for the opponent
+        let left = self.remaining(color, color) + self.inc;
+        if color {self.wtime = left} else {self.btime = left};
+        self.running = Some(Instant::now());
+    }
+
+    fn stop(&mut self, to_move: bool) {  // the time of the running move is taken off
+        let left = self.remaining(to_move, to_move);
+        if to_move {self.wtime = left} else {self.btime = left};
+        self.running = None;
+    }
+
+    fn reset(&mut self) {
+        *self = Clock::new(self.time, self.inc);
+    }
+}
+
+#[derive(Debug)]
+#[derive(Clone, PartialEq, Eq)]
+pub struct AnalysisLine {   // one line of the engine analysis
+    pub score: SFScore, // from the view of white, unlike the info of the engine, Mate(0) if the side to move is mated
+    pub san: Vec<String>,   // pv in san, ends before the first move which isn't legal
+    pub info: SearchInfo    // depth, nodes, pv in coordinate notation etc. as the engine sent it
+}
+
+#[derive(Debug)]
+pub enum NextError {    // errors of playing a move with next()
+    GameOver,
+    NotEngineTurn,  // a human is to move
+    Engine(SFErrors),
+    Move(MoveError),    // engine sent a move which can't be read
+    Update(UpdateError),
+    Exec(ExecError),
+    Machine(MachineErrors)  // next game of a match can't be set up
+}
+
+#[derive(Debug)]
+pub enum TakebackError {
+    NotEnoughMoves(usize),
+    Machine(MachineErrors)
+}
+
+#[derive(Debug)]
+pub enum ActionError {  // errors of resigning and draw offers
+    GameOver,
+    NoDrawOffer,
+    DrawOfferPending,
+    Engine(SFErrors)
+}
+
+#[derive(Debug)]
+pub enum PgnFileError { // errors when saving or loading a game
+    Pgn(PgnError),
+    Io(std::io::Error),
+    Machine(MachineErrors),
+    NoDir   // no pgn_dir and no home directory
 }
 
 #[derive(Debug)]
@@ -150,7 +379,20 @@ impl From<PFError> for ExecError {
 impl Game {
     pub fn new(xmtr: (bool, u8, u8, u8), ymtr: (bool, u8, u8, u8), magnet: u8) -> Result<Self, MachineErrors> {
         let machine = Machine::new(xmtr, ymtr, magnet)?;
-        Ok(Game { machine , wm: false, bm: false, ws: false, bs: false, welo: 1500, belo: 1500, sftime: 1000, currentmove: None })
+        let hash_history = vec![machine.position.repetition_key()];
+        let engine_config = match EngineConfig::load() {
+            Ok(c) => c,
+            Err(rr) => {
+                println!("Failed to read engine config: {:?}", rr);
+                EngineConfig::default()
+            }
+        };
+        let engine_path = stockfish::find_engine(&engine_config).unwrap_or(stockfish::DEFAULT_PATH.to_string());
+        Ok(Game { machine , wm: false, bm: false, ws: false, bs: false, welo: 1500, belo: 1500, sftime: 1000, currentmove: None, history: Vec::new(), hash_history, result: State::Normal, draw_offer: None, resign_threshold: 800, draw_margin: 30, pgn_dir: pgn_dir(), engine_path, engine_config, engine: None, clock: None, ponder: false, ponder_move: None, pending: None, sf_score: None, scores: Vec::new(), engine_match: None, match_engines: [None, None], user_settings: None })
+    }
+
+    pub fn dummy() -> Self {
+        Game { machine: Machine::dummy(), wm: false, bm: false, ws: false, bs: false, welo: 1500, belo: 1500, sftime: 1000, currentmove: None, history: Vec::new(), hash_history: vec![Position::new_reset().repetition_key()], result: State::Normal, draw_offer: None, resign_threshold: 800, draw_margin: 30, pgn_dir: None, engine_path: stockfish::DEFAULT_PATH.to_string(), engine_config: EngineConfig::default(), engine: None, clock: None, ponder: false, ponder_move: None, pending: None, sf_score: None, scores: Vec::new(), engine_match: None, match_engines: [None, None], user_settings: None }
     }
 
     pub fn set_settings(&mut self, set: (bool, bool, bool, bool, u32, u32, u32)) {
@@ -163,19 +405,88 @@ impl Game {
         self.sftime = set.6;
     }
 
-    pub fn next() {
+    #[allow(clippy::should_implement_trait)]
+    pub fn next(&mut self) -> Result<State, NextError> {   // sf plays one move and the machine carries it out if wm/bm is set, a match goes on with its next game
+        if self.result != State::Normal {
+            return if self.match_running() {self.next_match_game()} else {Err(NextError::GameOver)}
+        };
+        let color = self.get_current_color();
+        if !self.is_sf(color) {
+            return Err(NextError::NotEngineTurn)
+        };
+        let auto = (color && self.wm) || (!color && self.bm);  // before the move, the last one of a match brings back the settings of the user
+        let mv = match self.get_sf_move() {
+            Ok(SFResults::Normal(mv)) => mv,
+            Ok(_) => {  // no legal move, the position tells why
+                let state = self.machine.position.game_state();
+                self.finish(state);
+                return Ok(self.result)
+            },
+            Err(rr) => return Err(NextError::Engine(rr))
+        };
+        let score = self.sf_score.map(|(_, sc)| self.score_for(sc, true));
+        let ind_move = match ctim(&mv) {
+            Ok(im) => im,
+            Err(rr) => return Err(NextError::Move(rr))
+        };
+        let (state, pfi, oldpos) = match self.update(ind_move, &mv) {
+            Ok(res) => res,
+            Err(rr) => return Err(NextError::Update(rr))
+        };
+        self.scores.push(score);
+        if state == State::Normal && let Some(verdict) = self.engine_match.as_ref().and_then(|m| m.adjudication.judge(&self.scores)) {
+            self.finish(verdict);
+        };
+        if auto {
+            match self.execute_move(pfi, oldpos) {
+                Ok(_) => {},
+                Err(rr) => return Err(NextError::Exec(rr))
+            };
+        };
+        Ok(self.result)
+    }
+
+    pub fn start_match(&mut self, engine_match: EngineMatch) -> Result<(), MachineErrors> { // engine against engine, the machine carries out every move, next() plays them, ws/bs/wm/bm come back when the match is finished
+        self.match_engines = [None, None];
+        if self.user_settings.is_none() {
+            self.user_settings = Some((self.ws, self.bs, self.wm, self.bm));
+        };
+        self.ws = true;
+        self.bs = true;
+        self.wm = true;
+        self.bm = true;
+        let fen = engine_match.fen.clone().unwrap_or(Position::new_reset().to_fen());
+        self.engine_match = Some(engine_match);
+        self.set_position(&fen)
+    }
+
+    pub fn set_match_engine(&mut self, player: usize, engine: Engine) {    // uses an engine which is already running for a match player, e.g. one with a mock connection
+        self.match_engines[player] = Some(engine);
+    }
+
+    fn next_match_game(&mut self) -> Result<State, NextError> {
+        let fen = self.engine_match.as_ref().and_then(|m| m.fen.clone()).unwrap_or(Position::new_reset().to_fen());
+        match self.set_position(&fen) {
+            Ok(_) => Ok(self.result),
+            Err(rr) => Err(NextError::Machine(rr))
+        }
+    }
 
+    pub fn match_running(&self) -> bool {   // engines play both sides until the match is finished
+        self.engine_match.as_ref().is_some_and(|m| !m.finished())
     }
 
-    pub fn execute_move(&mut self, mov: Vec<PFIType>, mut oldpos: Position) -> Result<(), ExecError> {
+    fn match_player(&self, color: bool) -> Option<usize> { // player of the running match who has the given color
+        let white = self.engine_match.as_ref().filter(|m| !m.finished())?.white();
+        Some(if color {white} else {1 - white})
+    }
+
+    pub fn execute_move(&mut self, mov: Vec<PFIType>, oldpos: Position) -> Result<(), ExecError> {
         println!("Executing move: {:?}", mov);
         println!("Old position:");
         oldpos.print_out();
         println!("Current motor position: {:?}", self.machine.pos_mtr);
-        let mi = oldpos.pathfinding(&mov, &mut self.machine.pos_mtr)?;
-        println!("position now: {:?}", self.machine.pos_mtr);
-        mi.print_out();
-        self.machine.do_mi(mi);
+        self.machine.execute(&mov, oldpos)?;
         self.machine.print_status();
         Ok(())
     }
@@ -188,15 +499,1031 @@ impl Game {
         ctim(coordinate)
     }
 
+    pub fn parse_move(&self, text: &str) -> Result<String, MoveError> { // accepts coordinate notation or san, gives back coordinate notation as uci wants it, e.g. e7e8 => e7e8q
+        let pos = &self.machine.position;
+        let mv = if text.is_ascii() && (4..6).contains(&text.len()) && ctim(text).is_ok() {pos.legal_move(text)?} else {pos.from_san(text)?};
+        Ok(pos.move_to_coordinates(&mv))
+    }
+
+    pub fn to_san(&self, mov: &str) -> Result<String, MoveError> {  // san of a move in coordinate notation for the current position
+        self.machine.position.to_san(mov)
+    }
+
     pub fn check_possible_move(&self, mov: &str) -> Result<MoveType, MoveError> {
+        if self.result != State::Normal {
+            return Err(MoveError::GameOver)
+        };
         Ok(self.machine.position.validate_move_possibility(mov)?)
     }
 
-    pub fn update(&mut self, ind_move: ((usize, usize), (usize, usize)), coord_move: &str, elo: u32, time: u32) -> Result<(State, Vec<PFIType>, Position), UpdateError> {
-        self.machine.position.update(ind_move, coord_move, elo, time)
+    pub fn update(&mut self, ind_move: ((usize, usize), (usize, usize)), coord_move: &str) -> Result<(State, Vec<PFIType>, Position), UpdateError> {
+        if self.result != State::Normal {   // e.g. after a resignation the position still has legal moves
+            return Err(UpdateError::ImpossibleMove(MoveError::GameOver))
+        };
+        let coord_move = match self.machine.position.legal_move(coord_move) {  // history and engines get the move the same way whatever was typed, e.g. O-O as e1h1 in chess960
+            Ok(mv) => self.machine.position.move_to_coordinates(&mv),
+            Err(rr) => return Err(UpdateError::ImpossibleMove(rr))
+        };
+        let coord_move = coord_move.as_str();
+        let (mut state, pfi, oldpos) = self.machine.position.update(ind_move, coord_move)?;
+        if let Some(clock) = self.clock.as_mut() {
+            clock.press(oldpos.colorw);
+        };
+        if self.draw_offer == Some(self.machine.position.colorw) {   // the opponent moved instead of accepting
+            self.draw_offer = None;
+        };
+        self.history.push((oldpos.clone(), coord_move.to_string()));
+        let key = self.machine.position.repetition_key();
+        self.hash_history.push(key);
+        if let State::Normal | State::Draw(DrawR::FiftyMove) = state {
+            match self.hash_history.iter().filter(|k| **k == key).count() {
+                0..3 => {},
+                3..5 => state = State::Draw(DrawR::Repetition),
+                _ => state = State::Draw(DrawR::FivefoldRepetition)
+            }
+        };
+        self.finish(state);
+        if state == State::Normal {
+            self.think_ahead();
+        };
+        Ok((state, pfi, oldpos))
+    }
+
+    fn think_ahead(&mut self) { // lets sf think while the last move is carried out by the machine or the human is to move
+        let res = if self.is_sf(self.get_current_color()) {self.start_sf_search()} else {self.start_pondering()};
+        match res {
+            Ok(_) => {},
+            Err(rr) => println!("Failed to start engine search: {:?}", rr)
+        };
+    }
+
+    pub fn start_sf_search(&mut self) -> Result<bool, SFErrors> {  // starts the search of sf if it is to move, pondering on the played move goes on, the result comes with get_sf_move()
+        if self.result != State::Normal || !self.is_sf(self.get_current_color()) {
+            return Ok(false)
+        };
+        let (fen, color) = (self.machine.position.to_fen(), self.get_current_color());
+        if self.pending.as_deref() == Some(fen.as_str()) && self.engine_for(color)?.is_searching() {
+            if self.engine_for(color)?.is_pondering() {
+                self.engine_for(color)?.ponderhit()?;
+            };
+            return Ok(true)
+        };
+        self.go_sf()?;
+        Ok(true)
+    }
+
+    pub fn start_pondering(&mut self) -> Result<bool, SFErrors> {  // sf thinks on the reply it expects while the human is to move
+        let color = self.get_current_color();
+        if !self.ponder || self.result != State::Normal || self.is_sf(color) || !self.is_sf(!color) {
+            return Ok(false)
+        };
+        let (mv, after) = match self.ponder_move.clone().map(|mv| (self.machine.position.legal_move(&mv), mv)) {
+            Some((Ok(lm), mv)) => (mv, self.machine.position.make_move(&lm).to_fen()),
+            _ => return Ok(false)
+        };
+        let ((fen, moves), elo, limits) = (self.game_moves(), self.sf_elo(!color), self.sf_limits(!color));
+        let moves: Vec<&str> = moves.iter().map(|m| m.as_str()).collect();
+        self.engine_for(!color)?.go_ponder(&fen, &moves, &mv, elo, &limits)?;
+        self.pending = Some(after);
+        Ok(true)
+    }
+
+    fn go_sf(&mut self) -> Result<(), SFErrors> {   // starts a search for the side to move
+        let color = self.get_current_color();
+        let ((start, moves), elo, limits) = (self.game_moves(), self.sf_elo(color), self.sf_limits(color));
+        let moves: Vec<&str> = moves.iter().map(|m| m.as_str()).collect();
+        self.engine_for(color)?.go(&start, &moves, elo, &limits)?;
+        self.pending = Some(self.machine.position.to_fen());
+        Ok(())
+    }
+
+    fn game_moves(&self) -> (String, Vec<String>) { // start position of the game and the moves since, so that the engine knows about repetitions
+        let start = match self.history.first() {
+            Some((pos, _)) => pos.to_fen(),
+            None => self.machine.position.to_fen()
+        };
+        (start, self.history.iter().map(|(_, mv)| mv.clone()).collect())
+    }
+
+    fn cancel_thinking(&mut self) { // the position changed in another way than by a move or the game ended
+        self.pending = None;
+        self.ponder_move = None;
+        self.sf_score = None;
+        self.scores.clear();
+        let [m0, m1] = &mut self.match_engines;
+        for e in [&mut self.engine, m0, m1].into_iter().flatten() {
+            if e.is_searching() {
+                let _ = e.stop();
+            };
+        };
+    }
+
+    pub fn set_position(&mut self, fen: &str) -> Result<(), MachineErrors> {   // sets up a new position, the move history starts again
+        self.machine.set_position(fen)?;
+        self.history.clear();
+        self.hash_history = vec![self.machine.position.repetition_key()];
+        self.result = State::Normal;
+        self.draw_offer = None;
+        self.cancel_thinking();
+        self.new_engine_game();
+        if let Some(clock) = self.clock.as_mut() {
+            clock.reset();
+        };
+        Ok(())
+    }
+
+    pub fn set_clock(&mut self, clock: Option<Clock>) { // plays with the given clock from now on, it is started for the side to move
+        self.clock = clock;
+        if let Some(c) = self.clock.as_mut() {
+            c.running = Some(Instant::now());
+        };
+    }
+
+    pub fn remaining(&self, color: bool) -> Option<u32> {   // clock time of the given side, None without clock
+        self.clock.map(|c| c.remaining(color, self.get_current_color()))
+    }
+
+    pub fn start_chess960(&mut self, n: u16) -> Result<(), MachineErrors> {   // sets up the chess960 starting position with the given number (0..960)
+        match Position::new_chess960(n) {
+            Some(pos) => self.set_position(&pos.to_fen()),
+            None => Err(MachineErrors::Chess960(n))
+        }
+    }
+
+    pub fn takeback(&mut self, plies: usize) -> Result<(), TakebackError> {    // takes back the last moves, restores the position and moves the pieces back
+        if plies == 0 || plies > self.history.len() {
+            return Err(TakebackError::NotEnoughMoves(self.history.len()))
+        };
+        let target = self.history[self.history.len() - plies].0.clone();
+        match self.machine.arrange(&target) {
+            Ok(_) => {},
+            Err(rr) => return Err(TakebackError::Machine(rr))
+        };
+        self.history.truncate(self.history.len() - plies);
+        self.hash_history.truncate(self.hash_history.len() - plies);
+        self.currentmove = None;
+        self.result = State::Normal;
+        self.draw_offer = None;
+        self.cancel_thinking();
+        if let Some(clock) = self.clock.as_mut() {
+            clock.running = Some(Instant::now());
+        };
+        Ok(())
+    }
+
+    pub fn is_sf(&self, color: bool) -> bool { // whether the given side is played by sf
+        if color {self.ws} else {self.bs}
+    }
+
+    pub fn resign(&mut self, color: bool) -> Result<State, ActionError> {   // the given side resigns
+        if self.result != State::Normal {
+            return Err(ActionError::GameOver)
+        };
+        self.draw_offer = None;
+        self.finish(State::Resign(!color));
+        Ok(self.result)
+    }
+
+    pub fn offer_draw(&mut self, color: bool) -> Result<State, ActionError> {   // the given side offers a draw, sf answers at once, a human opponent with accept_draw() or decline_draw()
+        if self.result != State::Normal {
+            return Err(ActionError::GameOver)
+        };
+        if self.draw_offer.is_some() {
+            return Err(ActionError::DrawOfferPending)
+        };
+        self.draw_offer = Some(color);
+        if self.is_sf(!color) {
+            let eval = match self.get_sf_eval(!color) {
+                Ok(e) => e,
+                Err(rr) => {
+                    self.draw_offer = None;
+                    return Err(ActionError::Engine(rr))
+                }
+            };
+            let accept = match eval {
+                SFScore::Centipawns(cp) => cp <= self.draw_margin,
+                SFScore::Mate(n) => n <= 0
+            };
+            if accept {
+                return self.accept_draw()
+            };
+            self.draw_offer = None;
+        };
+        Ok(self.result)
+    }
+
+    pub fn accept_draw(&mut self) -> Result<State, ActionError> {  // the opponent of the offering side accepts
+        if self.result != State::Normal {
+            return Err(ActionError::GameOver)
+        };
+        match self.draw_offer.take() {
+            Some(_) => self.finish(State::Draw(DrawR::Agreement)),
+            None => return Err(ActionError::NoDrawOffer)
+        };
+        Ok(self.result)
+    }
+
+    pub fn decline_draw(&mut self) -> Result<(), ActionError> {
+        match self.draw_offer.take() {
+            Some(_) => Ok(()),
+            None => Err(ActionError::NoDrawOffer)
+        }
+    }
+
+    pub fn sf_resigns(&mut self) -> Result<State, ActionError> {   // lets sf resign if it is to move and its eval is below the threshold
+        let color = self.get_current_color();
+        if self.result != State::Normal || !self.is_sf(color) {
+            return Ok(self.result)
+        };
+        let give_up = match self.sf_score {  // score of its last move, an extra search would stop the one which runs already
+            Some((c, SFScore::Centipawns(cp))) if c == color => cp < -self.resign_threshold,
+            Some((c, SFScore::Mate(n))) if c == color => n < 0,
+            _ => false  // no own search yet, e.g. first move of the game or after a takeback
+        };
+        if give_up {
+            return self.resign(color)
+        };
+        Ok(self.result)
+    }
+
+    pub fn get_sf_eval(&mut self, color: bool) -> Result<SFScore, SFErrors> {  // eval of the current position from the view of the given side
+        let (fen, time) = (self.machine.position.to_fen(), self.sftime);
+        let eval = self.engine()?.get_eval(&fen, time)?;
+        Ok(self.score_for(eval, color))
+    }
+
+    pub fn analyse(&mut self, lines: u32) -> Result<Vec<AnalysisLine>, SFErrors> {  // the best lines of the current position at full strength, sf thinks sftime
+        let score = match self.machine.position.game_state() {  // no move left, the engine would send a score without pv
+            State::Mate(_) => Some(SFScore::Mate(0)),
+            State::Draw(DrawR::Stalemate) => Some(SFScore::Centipawns(0)),
+            _ => None
+        };
+        if let Some(score) = score {
+            return Ok(vec![AnalysisLine { score, san: Vec::new(), info: SearchInfo { depth: Some(0), score: Some(score), ..SearchInfo::default() } }])
+        };
+        let (fen, time) = (self.machine.position.to_fen(), self.sftime);
+        let infos = self.engine()?.analyse(&fen, lines, &SearchLimits::movetime(time))?;
+        let mut res = Vec::new();
+        for info in infos {
+            let score = match info.score {
+                Some(sc) => self.score_for(sc, true),
+                None => continue
+            };
+            let mut pos = self.machine.position.clone();
+            let mut san = Vec::new();
+            for mv in &info.pv {
+                match pos.legal_move(mv) {
+                    Ok(lm) => {
+                        san.push(pos.move_to_san(&lm));
+                        pos = pos.make_move(&lm);
+                    },
+                    Err(_) => break
+                };
+            };
+            res.push(AnalysisLine { score, san, info });
+        };
+        Ok(res)
+    }
+
+    pub fn hint(&mut self) -> Result<Option<String>, SFErrors> {    // best move for the side to move in san, None if there is none
+        Ok(self.analyse(1)?.into_iter().next().and_then(|l| l.san.into_iter().next()))
+    }
+
+    fn score_for(&self, score: SFScore, color: bool) -> SFScore {   // turns a score of the side to move to the view of the given side
+        if color == self.get_current_color() {
+            return score
+        };
+        match score {
+            SFScore::Centipawns(cp) => SFScore::Centipawns(-cp),
+            SFScore::Mate(n) => SFScore::Mate(-n)
+        }
+    }
+
+    fn finish(&mut self, state: State) {   // sets the result, a game which just ended gets saved, a result stays once it is there
+        if self.result != State::Normal {
+            return
+        };
+        let ended = state != State::Normal;
+        self.result = state;
+        if ended {
+            self.cancel_thinking();
+        };
+        match self.clock.as_mut() {
+            Some(clock) if ended => clock.stop(self.machine.position.colorw),
+            _ => {}
+        };
+        if ended && self.pgn_dir.is_some() {
+            match self.save_pgn() {
+                Ok(path) => println!("Game saved to {}", path),
+                Err(rr) => println!("Failed to save game: {:?}", rr)
+            }
+        };
+        if ended && let Some(m) = self.engine_match.as_mut() {  // after saving, the players change colors with the next game
+            m.record(state);
+            println!("{}", m.standings());
+            if m.finished() && let Some((ws, bs, wm, bm)) = self.user_settings.take() {
+                (self.ws, self.bs, self.wm, self.bm) = (ws, bs, wm, bm);
+            };
+        };
+    }
+
+    pub fn result_string(&self) -> &'static str {   // result as used in pgn
+        match self.result {
+            State::Normal => "*",
+            State::Mate(true) | State::Resign(true) => "1-0",
+            State::Mate(false) | State::Resign(false) => "0-1",
+            State::Draw(_) => "1/2-1/2"
+        }
+    }
+
+    pub fn to_pgn(&self) -> Result<String, PgnError> {  // game as pgn with seven tag roster, elo of sf and the start position if it isn't the usual one
+        let start = match self.history.first() {
+            Some((pos, _)) => pos.clone(),
+            None => self.machine.position.clone()
+        };
+        let secs = SystemTime::now().duration_since(UNIX_EPOCH).map(|d| d.as_secs()).unwrap_or(0);
+        let (y, m, d) = date_from_days((secs / 86400) as i64);
+        let player = |color: bool| match self.match_player(color) {
+            Some(p) => self.engine_match.as_ref().unwrap().players[p].name.clone(),
+            None => String::from(if (color && self.ws) || (!color && self.bs) {"Stockfish"} else {"Player"})
+        };
+        let (event, round) = match &self.engine_match {
+            Some(m) => (String::from("chess_firmware engine match"), (m.played() + 1).to_string()),
+            None => (String::from("chess_firmware game"), String::from("-"))
+        };
+        let mut tags: Vec<(String, String)> = vec![
+            (String::from("Event"), event),
+            (String::from("Site"), String::from("?")),
+            (String::from("Date"), format!("{:04}.{:02}.{:02}", y, m, d)),
+            (String::from("Round"), round),
+            (String::from("White"), player(true)),
+            (String::from("Black"), player(false)),
+            (String::from("Result"), self.result_string().to_string())
+        ];
+        if let (true, Some(elo)) = (self.ws, self.sf_elo(true)) {
+            tags.push((String::from("WhiteElo"), elo.to_string()));
+        };
+        if let (true, Some(elo)) = (self.bs, self.sf_elo(false)) {
+            tags.push((String::from("BlackElo"), elo.to_string()));
+        };
+        if start.chess960 {
+            tags.push((String::from("Variant"), String::from("Chess960")));
+        };
+        let fen = start.to_fen();
+        if fen != Position::new_reset().to_fen() {
+            tags.push((String::from("SetUp"), String::from("1")));
+            tags.push((String::from("FEN"), fen));
+        };
+        let moves: Vec<String> = self.history.iter().map(|(_, m)| m.clone()).collect();
+        pgn::write(&tags, &start, &moves, self.result_string())
+    }
+
+    pub fn save_pgn(&self) -> Result<String, PgnFileError> { // writes the game into a new file in pgn_dir, gives back the path
+        let dir = match self.pgn_dir.clone().or_else(pgn_dir) {
+            Some(d) => d,
+            None => return Err(PgnFileError::NoDir)
+        };
+        let text = match self.to_pgn() {
+            Ok(t) => t,
+            Err(rr) => return Err(PgnFileError::Pgn(rr))
+        };
+        match fs::create_dir_all(&dir) {
+            Ok(_) => {},
+            Err(rr) => return Err(PgnFileError::Io(rr))
+        };
+        let stamp = SystemTime::now().duration_since(UNIX_EPOCH).map(|d| d.as_secs()).unwrap_or(0);
+        for n in 0.. {  // games which end in the same second get a number, no file is overwritten
+            let path = if n == 0 {format!("{}/game_{}.pgn", dir, stamp)} else {format!("{}/game_{}_{}.pgn", dir, stamp, n)};
+            match fs::OpenOptions::new().write(true).create_new(true).open(&path) {
+                Ok(mut file) => return match file.write_all(text.as_bytes()) {
+                    Ok(_) => Ok(path),
+                    Err(rr) => Err(PgnFileError::Io(rr))
+                },
+                Err(rr) if rr.kind() == ErrorKind::AlreadyExists => continue,
+                Err(rr) => return Err(PgnFileError::Io(rr))
+            };
+        };
+        unreachable!()
+    }
+
+    pub fn load_pgn(&mut self, text: &str) -> Result<State, PgnFileError> {    // loads a game, the pieces get moved to the final position
+        let game = match pgn::read(text) {
+            Ok(g) => g,
+            Err(rr) => return Err(PgnFileError::Pgn(rr))
+        };
+        match self.machine.arrange(&game.position) {
+            Ok(_) => {},
+            Err(rr) => return Err(PgnFileError::Machine(rr))
+        };
+        self.hash_history = game.history.iter().map(|(pos, _)| pos.repetition_key()).collect();
+        self.hash_history.push(game.position.repetition_key());
+        self.history = game.history;
+        self.currentmove = None;
+        self.draw_offer = None;
+        self.cancel_thinking();
+        self.new_engine_game();
+        self.result = match (game.position.game_state(), game.result.as_str()) {
+            (State::Normal, "1-0") => State::Resign(true),
+            (State::Normal, "0-1") => State::Resign(false),
+            (State::Normal, "1/2-1/2") => State::Draw(DrawR::Agreement),
+            (st, _) => st
+        };
+        Ok(self.result)
+    }
+
+    pub fn get_sf_move(&mut self) -> Result<SFResults, SFErrors> {  // result of the search started by start_sf_search(), a new search if there is none
+        let (fen, color) = (self.machine.position.to_fen(), self.get_current_color());
+        let running = self.pending.as_deref() == Some(fen.as_str()) && self.engine_for(color)?.is_searching();
+        if !running {
+            self.go_sf()?;
+        } else if self.engine_for(color)?.is_pondering() {
+            self.engine_for(color)?.ponderhit()?;
+        };
+        self.pending = None;
+        let res = self.engine_for(color)?.wait()?;
+        self.ponder_move = res.ponder.clone();
+        self.sf_score = res.score().map(|sc| (color, sc));
+        res.result()
+    }
+
+    fn sf_elo(&self, color: bool) -> Option<u32> {  // strength of the engine playing the given side
+        match self.match_player(color) {
+            Some(p) => self.engine_match.as_ref().unwrap().players[p].elo,
+            None => Some(if color {self.welo} else {self.belo})
+        }
+    }
+
+    fn sf_limits(&self, color: bool) -> SearchLimits {  // with a clock sf manages its time itself, otherwise it thinks sftime or the time of the match player
+        let time = match self.match_player(color) {
+            Some(p) => self.engine_match.as_ref().unwrap().players[p].time,
+            None => self.sftime
+        };
+        match (self.remaining(true), self.remaining(false), self.clock) {
+            (Some(wtime), Some(btime), Some(c)) => SearchLimits::clock(wtime, btime, c.inc, c.inc),
+            _ => SearchLimits::movetime(time)
+        }
+    }
+
+    fn engine_for(&mut self, color: bool) -> Result<&mut Engine, SFErrors> { // engine which plays the given side, the one of the match player or sf
+        let player = match self.match_player(color) {
+            Some(p) => p,
+            None => return self.engine()
+        };
+        let engine = match self.match_engines[player].take() {
+            Some(e) => e,
+            None => {
+                let conf = &self.engine_match.as_ref().unwrap().players[player];
+                start_engine(&conf.path, &conf.config)?    // no built-in engine instead, its games would count for the wrong player
+            }
+        };
+        Ok(self.match_engines[player].insert(engine))
+    }
+
+    fn engine(&mut self) -> Result<&mut Engine, SFErrors> { // running sf, started on first use
+        let engine = match self.engine.take() {
+            Some(e) => e,
+            None => {
+                start_engine_or_native(&self.engine_path, &self.engine_config)?
+            }
+        };
+        Ok(self.engine.insert(engine))
+    }
+
+    pub fn set_engine_path(&mut self, path: &str) -> Result<String, SFErrors> { // switches to the uci engine at the given path or to the built-in one with native::NAME, gives back its name
+        let engine = start_engine(path, &self.engine_config)?;
+        let name = engine.name.clone();
+        self.engine_path = path.to_string();
+        self.engine = Some(engine);
+        Ok(name)
+    }
+
+    pub fn set_engine(&mut self, engine: Engine) {  // uses an engine which is already running, e.g. one with a mock connection
+        self.engine_path = engine.path.clone();
+        self.engine = Some(engine);
+    }
+
+    pub fn set_engine_config(&mut self, config: EngineConfig) -> Result<(), SFErrors> {  // Threads, Hash, SyzygyPath and Skill Level, a running engine gets them at once
+        if let Some(e) = self.engine.as_mut() {
+            e.configure(&config)?;
+        };
+        self.engine_config = config;
+        Ok(())
+    }
+
+    pub fn save_engine_config(&mut self) -> Result<PathBuf, ConfigError> { // stores engine path and options, they are used again at the next start
+        self.engine_config.path = Some(self.engine_path.clone());
+        self.engine_config.save()
+    }
+
+    pub fn set_engine_option(&mut self, name: &str, value: OptionValue) -> Result<(), SFErrors> {  // sets an option of the engine, it stays set for the whole session
+        self.engine()?.set_option(name, value)
+    }
+
+    fn new_engine_game(&mut self) { // a new game or another position, sf and the match engines shouldn't use their old hash
+        let [m0, m1] = &mut self.match_engines;
+        for e in [&mut self.engine, m0, m1].into_iter().flatten() {
+            e.new_game();
+        };
+    }
+}
+
+pub fn pgn_dir() -> Option<String> {   // default directory for saved games, found at runtime like stockfish::config_path(), None without a home directory
+    match (env::var_os("XDG_DATA_HOME"), env::var_os("HOME")) {
+        (Some(dir), _) if !dir.is_empty() => Some(PathBuf::from(dir).join(PGN_DIR).to_string_lossy().to_string()),
+        (_, Some(home)) => Some(PathBuf::from(home).join(".local/share").join(PGN_DIR).to_string_lossy().to_string()),
+        _ => None
+    }
+}
+
+fn start_engine(path: &str, config: &EngineConfig) -> Result<Engine, SFErrors> {  // uci engine at the path or the built-in one for native::NAME, with the options of the config
+    let mut engine = if path == native::NAME {
+        let mut e = Engine::with_connection(Box::new(NativeEngine::new(native::DEFAULT_DEPTH)))?;
+        e.path = path.to_string();
+        e
+    } else {
+        Engine::new(path)?
+    };
+    engine.configure(config)?;
+    Ok(engine)
+}
+
+fn start_engine_or_native(path: &str, config: &EngineConfig) -> Result<Engine, SFErrors> {    // the built-in engine plays if the binary can't be started, e.g. because it is missing
+    match start_engine(path, config) {
+        Err(SFErrors::CreationError(rr)) => {
+            println!("Engine {} can't be started ({}), using the built-in engine", path, rr);
+            start_engine(native::NAME, config)
+        },
+        res => res
     }
+}
+
+fn date_from_days(days: i64) -> (i64, u32, u32) {   // converts days since 1970-01-01 into year, month and day
+    let z = days + 719468;
+    let era = z.div_euclid(146097);
+    let doe = z.rem_euclid(146097);
+    let yoe = (doe - doe / 1460 + doe / 36524 - doe / 146096) / 365;
+    let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+    let mp = (5 * doy + 2) / 153;
+    let d = (doy - (153 * mp + 2) / 5 + 1) as u32;
+    let m = if mp < 10 { mp + 3 } else { mp - 9 } as u32;
+    (yoe + era * 400 + if m <= 2 { 1 } else { 0 }, m, d)
+}
+
+pub mod native {    // built-in engine of the position crate behind the uci interface, plays when no engine binary can be started
+    use std::{sync::{atomic::{AtomicBool, Ordering}, mpsc::{self, Receiver, RecvTimeoutError, Sender}, Arc}, thread::{self, JoinHandle}, time::{Duration, Instant}};
+    use position::position::Position;
+    use position::search::{mate_in, Iteration, Limits, Searcher};
+    use stockfish::{Connection, SFErrors};
+
+    pub const NAME: &str = "native";    // engine path which selects this engine
+    pub const DEFAULT_DEPTH: u32 = 6;
+    const MAX_DEPTH: u32 = 20;
 
-    pub fn get_sf_move(&self) -> Result<SFResults, SFErrors> {
-        stockfish::get_move(&self.machine.position.to_fen(), if self.machine.position.colorw {self.welo} else {self.belo}, self.sftime)
+    pub struct NativeEngine {
+        depth: u32, // deepest iteration, uci option Depth
+        elo: Option<u32>,   // limited strength plays with less depth, see elo_depth()
+        position: Position,
+        keys: Vec<u64>, // hashes of the positions before the current one, for repetitions
+        stop: Arc<AtomicBool>,
+        release: Arc<AtomicBool>,   // ponderhit or stop, a ponder or infinite search may give its bestmove
+        search: Option<JoinHandle<()>>,
+        tx: Sender<String>,
+        rx: Receiver<String>,   // output lines, written by the search thread
+        alive: bool
     }
-}
\ No newline at end of file
+
+    impl NativeEngine {
+        pub fn new(depth: u32) -> Self {
+            let (tx, rx) = mpsc::channel();
+            NativeEngine { depth: depth.clamp(1, MAX_DEPTH), elo: None, position: Position::new_reset(), keys: Vec::new(), stop: Arc::new(AtomicBool::new(false)), release: Arc::new(AtomicBool::new(false)), search: None, tx, rx, alive: true }
+        }
+
+        fn elo_depth(&self) -> u32 {    // 1320 => 2, 1500 => 3, 2000 => 6, never deeper than the Depth option
+            match self.elo {
+                Some(elo) => (elo.saturating_sub(1000) / 200 + 1).clamp(1, self.depth),
+                None => self.depth
+            }
+        }
+
+        fn output(&self, line: &str) {
+            let _ = self.tx.send(line.to_string());
+        }
+
+        fn set_option(&mut self, line: &str) {  // e.g. setoption name Depth value 4, unknown options are ignored
+            let (name, value) = match (line.find(" name "), line.find(" value ")) {
+                (Some(n), Some(v)) if n < v => (line[n + 6..v].trim(), line[v + 7..].trim()),
+                _ => return
+            };
+            match name.to_ascii_lowercase().as_str() {
+                "depth" => if let Ok(d) = value.parse::<u32>() {
+                    self.depth = d.clamp(1, MAX_DEPTH);
+                },
+                "uci_limitstrength" if value == "false" => self.elo = None,
+                "uci_limitstrength" => self.elo = Some(self.elo.unwrap_or(1500)),
+                "uci_elo" => if let Ok(e) = value.parse::<u32>() {
+                    self.elo = self.elo.map(|_| e);
+                },
+                _ => {}
+            };
+        }
+
+        fn set_position(&mut self, words: &[&str]) {    // position startpos|fen <fen> [moves ...], the old position stays if the new one is invalid
+            let (mut pos, rest) = match words {
+                ["startpos", rest @ ..] => (Position::new_reset(), rest),
+                ["fen", rest @ ..] => {
+                    let end = rest.iter().position(|w| *w == "moves").unwrap_or(rest.len());
+                    match Position::from_fen(&rest[..end].join(" ")) {
+                        Ok(p) => (p, &rest[end..]),
+                        Err(rr) => return self.output(&format!("info string invalid fen: {:?}", rr))
+                    }
+                },
+                _ => return
+            };
+            let mut keys = Vec::new();
+            for mv in rest.iter().skip(1) {
+                match pos.legal_move(mv) {
+                    Ok(lm) => {
+                        keys.push(pos.hash);
+                        pos = pos.make_move(&lm);
+                    },
+                    Err(rr) => return self.output(&format!("info string invalid move {}: {:?}", mv, rr))
+                };
+            };
+            self.position = pos;
+            self.keys = keys;
+        }
+
+        fn go(&mut self, words: &[&str]) {  // starts the search thread, ponder and infinite searches hold back the bestmove until ponderhit or stop
+            self.halt();
+            while self.rx.try_recv().is_ok() {}  // bestmove of the old search, nobody waits for it anymore
+            let number = |name: &str| words.iter().position(|w| *w == name).and_then(|i| words.get(i + 1)).and_then(|v| v.parse::<u64>().ok());
+            let hold = words.contains(&"ponder") || words.contains(&"infinite");
+            let own = if self.position.colorw {(number("wtime"), number("winc"))} else {(number("btime"), number("binc"))};
+            let time = match (number("movetime"), own) {
+                (Some(t), _) => Some(t),
+                (None, (Some(t), inc)) => Some((t / number("movestogo").unwrap_or(30).max(1) + inc.unwrap_or(0) / 2).min(t * 4 / 5)),
+                _ => None
+            };
+            let depth = number("depth").map(|d| d as u32).unwrap_or(MAX_DEPTH).min(self.elo_depth());
+            let limits = Limits { depth, nodes: number("nodes"), deadline: if hold {None} else {time.map(|t| Instant::now() + Duration::from_millis(t))} };
+            self.stop = Arc::new(AtomicBool::new(false));
+            self.release = Arc::new(AtomicBool::new(!hold));
+            let (pos, keys, stop, release, tx) = (self.position.clone(), self.keys.clone(), Arc::clone(&self.stop), Arc::clone(&self.release), self.tx.clone());
+            self.search = Some(thread::spawn(move || {
+                let res = Searcher::new(limits).with_stop(stop).with_history(keys).search(&pos, |it| {
+                    let _ = tx.send(info_line(&pos, it));
+                });
+                while !release.load(Ordering::Relaxed) {
+                    thread::sleep(Duration::from_millis(5));
+                };
+                let line = match res {
+                    Some(it) => match it.pv.get(1) {
+                        Some(ponder) => format!("bestmove {} ponder {}", pos.move_to_coordinates(&it.pv[0]), pos.move_to_coordinates(ponder)),
+                        None => format!("bestmove {}", pos.move_to_coordinates(&it.pv[0]))
+                    },
+                    None => format!("info depth 0 score {}\nbestmove (none)", if pos.in_check(pos.colorw) {"mate 0"} else {"cp 0"})
+                };
+                for l in line.lines() {
+                    let _ = tx.send(l.to_string());
+                };
+            }));
+        }
+
+        fn halt(&mut self) {    // ends the running search, its bestmove is still sent
+            self.stop.store(true, Ordering::Relaxed);
+            self.release.store(true, Ordering::Relaxed);
+            if let Some(handle) = self.search.take() {
+                let _ = handle.join();
+            };
+        }
+    }
+
+    fn info_line(pos: &Position, it: &Iteration) -> String {    // e.g. info depth 4 score cp 35 nodes 7158 pv b1c3 b8c6, pos only tells whether it's chess960
+        let score = match mate_in(it.score) {
+            Some(n) => format!("mate {}", n),
+            None => format!("cp {}", it.score)
+        };
+        let pv: Vec<String> = it.pv.iter().map(|mv| pos.move_to_coordinates(mv)).collect();
+        format!("info depth {} score {} nodes {} pv {}", it.depth, score, it.nodes, pv.join(" "))
+    }
+
+    impl Connection for NativeEngine {
+        fn send(&mut self, commands: &str) -> Result<(), SFErrors> {
+            for line in commands.lines() {
+                let words: Vec<&str> = line.split_whitespace().collect();
+                match words.as_slice() {
+                    ["uci"] => {
+                        for l in ["id name chess_firmware native", "id author chess_firmware", &format!("option name Depth type spin default {} min 1 max {}", self.depth, MAX_DEPTH),
+                            "option name UCI_Chess960 type check default false", "option name UCI_LimitStrength type check default false", "option name UCI_Elo type spin default 1500 min 1000 max 2400", "uciok"] {
+                            self.output(l);
+                        };
+                    },
+                    ["isready"] => self.output("readyok"),
+                    ["setoption", ..] => self.set_option(line),
+                    ["position", rest @ ..] => self.set_position(rest),
+                    ["go", rest @ ..] => self.go(rest),
+                    ["stop"] => self.halt(),
+                    ["ponderhit"] => self.release.store(true, Ordering::Relaxed),
+                    ["quit"] => {
+                        self.halt();
+                        self.alive = false;
+                    },
+                    _ => {} // ucinewgame and unknown commands
+                };
+            };
+            Ok(())
+        }
+
+        fn read_line(&mut self, timeout: Option<Duration>) -> Result<String, SFErrors> {
+            match (timeout, self.rx.try_recv()) {
+                (_, Ok(line)) => Ok(line),
+                _ if !self.alive => Err(SFErrors::SFDied),
+                (Some(t), _) => match self.rx.recv_timeout(t) {
+                    Ok(line) => Ok(line),
+                    Err(RecvTimeoutError::Timeout) => Err(SFErrors::Timeout),
+                    Err(RecvTimeoutError::Disconnected) => Err(SFErrors::SFDied)
+                },
+                (None, _) => self.rx.recv().map_err(|_| SFErrors::SFDied)
+            }
+        }
+
+        fn try_read_line(&mut self) -> Option<String> {
+            self.rx.try_recv().ok()
+        }
+
+        fn is_alive(&mut self) -> bool {
+            self.alive
+        }
+
+        fn kill(&mut self) {
+            self.halt();
+            self.alive = false;
+        }
+
+        fn close(&mut self) {
+            self.halt();
+        }
+
+        fn reconnect(&mut self) -> Result<Box<dyn Connection>, SFErrors> {
+            Ok(Box::new(NativeEngine::new(self.depth)))
+        }
+    }
+}
+
+#[cfg(test)]    // games against the mock engine, no hardware and no engine binary needed
+mod tests {
+
+    use super::{native::{self, NativeEngine}, Adjudication, Clock, EngineConfig, EngineMatch, EnginePlayer, Game, NextError};
+    use position::position::{ctim, DrawR, MoveError, Position, State, UpdateError};
+    use stockfish::{mock::MockEngine, Engine, SearchLimits, SFErrors, SFResults, SFScore};
+
+    fn game_with(mock: MockEngine) -> Game {
+        let mut game = Game::dummy();
+        game.set_engine(Engine::with_connection(Box::new(mock)).unwrap());
+        game
+    }
+
+    fn play_sf_move(game: &mut Game) -> State {
+        let mv = match game.get_sf_move().unwrap() {
+            SFResults::Normal(mv) => mv,
+            res => panic!("no move: {:?}", res)
+        };
+        game.update(ctim(&mv).unwrap(), &mv).unwrap().0
+    }
+
+    #[test]
+    fn it_works() {
+        let mut mock = MockEngine::new();
+        for mv in ["f2f3", "e7e5", "g2g4", "d8h4"] {
+            mock = mock.on("go", &[&format!("bestmove {}", mv)]);
+        };
+        let mut game = game_with(mock);
+        game.ws = true;
+        game.bs = true;
+        let states: Vec<State> = (0..4).map(|_| play_sf_move(&mut game)).collect();
+        assert_eq!(states, vec![State::Normal, State::Normal, State::Normal, State::Mate(false)]);
+        assert_eq!(game.result, State::Mate(false));
+        assert_eq!(game.history.len(), 4);
+        assert!(game.to_pgn().unwrap().contains("1. f3 e5 2. g4 Qh4# 0-1"));
+    }
+
+    #[test]
+    fn it_works2() {
+        let mock = MockEngine::new()
+            .on("go", &["info depth 18 multipv 1 score cp -30 pv e7e5 g1f3 b8c6", "info depth 18 multipv 2 score mate -4 pv f7f6 d1h5", "bestmove e7e5"]);
+        let mut game = game_with(mock);
+        game.update(ctim("e2e4").unwrap(), "e2e4").unwrap();
+        let lines = game.analyse(2).unwrap();
+        assert_eq!(lines.len(), 2);
+        assert_eq!((lines[0].score, lines[0].san.join(" ")), (SFScore::Centipawns(30), String::from("e5 Nf3 Nc6")));
+        assert_eq!((lines[1].score, lines[1].san.join(" ")), (SFScore::Mate(4), String::from("f6 Qh5+")));
+        assert_eq!(game.hint().unwrap(), Some(String::from("e5")));
+    }
+
+    #[test]
+    fn it_works3() {
+        let mock = MockEngine::new()
+            .on("go", &["info depth 20 score cp -150 pv e2e4", "bestmove e2e4"])
+            .on("go", &["info depth 20 score cp 10 pv e2e4", "bestmove e2e4"]);
+        let mut game = game_with(mock);
+        game.bs = true;
+        assert_eq!(game.offer_draw(true).unwrap(), State::Normal);
+        assert_eq!(game.draw_offer, None);
+        assert_eq!(game.offer_draw(true).unwrap(), State::Draw(DrawR::Agreement));
+    }
+
+    #[test]
+    fn it_works4() {
+        let mock = MockEngine::new().on("go", &["bestmove e2e4"]);
+        let log = mock.log();
+        let mut game = game_with(mock);
+        game.ws = true;
+        game.set_clock(Some(Clock::new(60000, 1000)));
+        play_sf_move(&mut game);
+        let go = log.lock().unwrap().last().unwrap().clone();
+        assert!(go.starts_with("go wtime ") && go.ends_with("winc 1000 binc 1000"));
+        let clock = game.clock.unwrap();
+        assert!(clock.wtime > 60000 && clock.wtime <= 61000);
+        assert_eq!(game.remaining(false).map(|t| t <= 60000), Some(true));
+    }
+
+    #[test]
+    fn it_works5() {    // sf plays black and ponders, the first expected reply comes, the second doesn't
+        let mock = MockEngine::new()
+            .on("go", &["info depth 20 score cp -20 pv e7e5 g1f3", "bestmove e7e5 ponder g1f3"])
+            .on("go", &[])
+            .on("go", &[])
+            .on("go", &["bestmove e5d4"])
+            .on("ponderhit", &["bestmove b8c6 ponder f1b5"])
+            .on("stop", &["bestmove a7a6"]);
+        let log = mock.log();
+        let mut game = game_with(mock);
+        game.bs = true;
+        game.ponder = true;
+        let mut moves = Vec::new();
+        for human in ["e2e4", "g1f3", "d2d4"] {
+            game.update(ctim(human).unwrap(), human).unwrap();
+            moves.push(play_sf_move(&mut game));
+        };
+        assert_eq!(game.history.iter().map(|(_, m)| m.as_str()).collect::<Vec<&str>>(), vec!["e2e4", "e7e5", "g1f3", "b8c6", "d2d4", "e5d4"]);
+        let log = log.lock().unwrap();
+        assert!(log.iter().any(|l| l == "position fen rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 0 1 moves e2e4 e7e5 g1f3"));   // ponders with the whole game
+        assert!(log.iter().any(|l| l == "position fen rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 0 1 moves e2e4 e7e5 g1f3 b8c6 d2d4"));
+        assert_eq!(log.iter().filter(|l| l.starts_with("go ponder")).count(), 2);
+        assert_eq!(log.iter().filter(|l| l.as_str() == "ponderhit").count(), 1);
+        assert_eq!(log.iter().filter(|l| l.as_str() == "stop").count(), 1);
+    }
+
+    #[test]
+    fn it_works6() {
+        let adj = Adjudication { resign_score: 500, resign_plies: 3, draw_score: 10, draw_plies: 4, draw_from: 6, max_plies: 20 };
+        let cp = |c: i32| Some(SFScore::Centipawns(c));
+        assert_eq!(adj.judge(&[cp(600), cp(700), cp(650)]), Some(State::Resign(true)));
+        assert_eq!(adj.judge(&[cp(-600), Some(SFScore::Mate(-3)), cp(-900)]), Some(State::Resign(false)));
+        assert_eq!(adj.judge(&[cp(600), None, cp(650)]), None);
+        assert_eq!(adj.judge(&[cp(0), cp(5), cp(-5), cp(0)]), None);
+        assert_eq!(adj.judge(&[cp(30), cp(40), cp(0), cp(5), cp(-5), cp(0)]), Some(State::Draw(DrawR::Adjudication)));
+        assert_eq!(adj.judge(&[cp(100); 20]), Some(State::Draw(DrawR::Adjudication)));
+    }
+
+    #[test]
+    fn it_works7() {    // first game of a match, the second player mates with black
+        let player = |name: &str| EnginePlayer { name: name.to_string(), path: String::new(), elo: None, config: EngineConfig::default(), time: 100 };
+        let engine = |moves: [&str; 2]| {
+            let mut mock = MockEngine::new();
+            for mv in moves {
+                mock = mock.on("go", &[&format!("bestmove {}", mv)]);
+            };
+            Engine::with_connection(Box::new(mock)).unwrap()
+        };
+        let mut game = Game::dummy();
+        game.bs = true;
+        game.user_settings = Some((game.ws, game.bs, game.wm, game.bm));  // like start_match(), which needs the machine for setting up the board
+        game.ws = true;
+        game.engine_match = Some(EngineMatch::new([player("Alpha"), player("Beta")], 1));
+        game.set_match_engine(0, engine(["f2f3", "g2g4"]));
+        game.set_match_engine(1, engine(["e7e5", "d8h4"]));
+        let states: Vec<State> = (0..4).map(|_| game.next().unwrap()).collect();
+        assert_eq!(states, vec![State::Normal, State::Normal, State::Normal, State::Mate(false)]);
+        let m = game.engine_match.as_ref().unwrap();
+        assert_eq!((m.wins, m.draws, m.finished()), ([0, 1], 0, true));
+        assert_eq!(m.standings(), "Alpha 0 - 1 Beta (1 of 1 games)");
+        assert!(matches!(game.next(), Err(NextError::GameOver)));
+        assert_eq!((game.ws, game.bs), (false, true));  // settings of the user are back
+    }
+
+    #[test]
+    fn it_works8() {    // built-in engine behind the uci interface
+        let mut engine = Engine::with_connection(Box::new(NativeEngine::new(3))).unwrap();
+        assert_eq!(engine.name, "chess_firmware native");
+        let mate = "6k1/5ppp/8/8/8/8/5PPP/R5K1 w - - 0 1";
+        let res = engine.search(mate, None, &SearchLimits::depth(3)).unwrap();
+        assert_eq!((res.bestmove.as_deref(), res.score()), (Some("a1a8"), Some(SFScore::Mate(1))));
+        assert_eq!(engine.get_move_with("7k/5Q2/6K1/8/8/8/8/8 b - - 0 1", Some(1500), &SearchLimits::movetime(100)).unwrap(), SFResults::Stalemate);
+        assert_eq!(engine.get_move_with("R5k1/5ppp/8/8/8/8/5PPP/6K1 b - - 1 1", None, &SearchLimits::movetime(100)).unwrap(), SFResults::Mate);
+        // infinite search and pondering only end on request
+        engine.go(mate, &[], None, &SearchLimits::infinite()).unwrap();
+        assert_eq!(engine.stop().unwrap().bestmove.as_deref(), Some("a1a8"));
+        engine.go_ponder("4k3/8/8/3q4/8/8/8/3RK3 b - - 0 1", &[], "d5d1", None, &SearchLimits::movetime(1000)).unwrap();
+        engine.ponderhit().unwrap();
+        assert!(engine.wait().unwrap().bestmove.is_some());
+    }
+
+    #[test]
+    fn it_works9() {    // without the engine binary the built-in engine plays
+        let mut game = Game::dummy();
+        game.engine_path = String::from("/nonexistent/engine");
+        game.ws = true;
+        game.sftime = 200;
+        match game.get_sf_move().unwrap() {
+            SFResults::Normal(mv) => assert!(game.machine.position.legal_move(&mv).is_ok()),
+            res => panic!("no move: {:?}", res)
+        };
+        assert_eq!(game.engine_path, "/nonexistent/engine");
+        assert_eq!(game.set_engine_path(native::NAME).unwrap(), "chess_firmware native");
+        assert!(game.set_engine_path("/nonexistent/engine").is_err());
+    }
+
+    #[test]
+    fn it_works10() {   // a match player whose engine can't be started doesn't get replaced
+        let player = EnginePlayer { name: String::from("Missing"), path: String::from("/nonexistent/engine"), elo: None, config: EngineConfig::default(), time: 100 };
+        let mut game = Game::dummy();
+        game.ws = true;
+        game.bs = true;
+        game.engine_match = Some(EngineMatch::new([player.clone(), player], 1));
+        assert!(matches!(game.next(), Err(NextError::Engine(SFErrors::CreationError(_)))));
+    }
+
+    #[test]
+    fn it_works11() {   // sf resigns on the score of its own last search and never starts an extra one
+        let mock = MockEngine::new().on("go", &["info depth 10 score cp -900 pv e2e4", "bestmove e2e4"]);
+        let log = mock.log();
+        let mut game = game_with(mock);
+        game.ws = true;
+        assert_eq!(game.sf_resigns().unwrap(), State::Normal);
+        assert!(!log.lock().unwrap().iter().any(|l| l.starts_with("go")));
+        play_sf_move(&mut game);
+        game.update(ctim("e7e5").unwrap(), "e7e5").unwrap();
+        assert_eq!(game.sf_resigns().unwrap(), State::Resign(false));
+    }
+
+    #[test]
+    fn it_works12() {   // no moves after the end of the game, the result stays
+        let mut game = Game::dummy();
+        game.resign(true).unwrap();
+        assert!(matches!(game.check_possible_move("e2e4"), Err(MoveError::GameOver)));
+        assert!(matches!(game.update(ctim("e2e4").unwrap(), "e2e4"), Err(UpdateError::ImpossibleMove(MoveError::GameOver))));
+        game.finish(State::Normal);
+        assert_eq!((game.result, game.history.len()), (State::Resign(false), 0));
+        assert!(game.to_pgn().unwrap().trim_end().ends_with("0-1"));
+    }
+
+    #[test]
+    fn it_works13() {   // the engine gets the moves in uci form, whatever the human typed
+        let mock = MockEngine::new().on("go", &["bestmove h7h6"]);
+        let log = mock.log();
+        let mut game = game_with(mock);
+        game.bs = true;
+        game.machine.position = Position::from_fen("8/4P2k/8/8/8/8/8/4K3 w - - 0 1").unwrap();
+        assert!(game.parse_move("e7e8qq").is_err());
+        assert_eq!(game.parse_move("e7e8").unwrap(), "e7e8q");
+        game.update(ctim("e7e8").unwrap(), "e7e8").unwrap();
+        assert_eq!(game.history[0].1, "e7e8q");
+        assert!(log.lock().unwrap().iter().any(|l| l == "position fen 8/4P2k/8/8/8/8/8/4K3 w - - 0 1 moves e7e8q"));
+        // chess960 with the usual start squares, rochade is king takes rook
+        let mock = MockEngine::new().on("go", &["bestmove a7a6"]);
+        let log = mock.log();
+        let mut game = game_with(mock);
+        game.bs = true;
+        game.machine.position = Position::from_fen("r3k2r/pppppppp/8/8/8/8/PPPPPPPP/R3K2R w HAha - 0 1").unwrap();
+        assert_eq!(game.parse_move("O-O").unwrap(), "e1h1");
+        game.update(ctim("e1g1").unwrap(), "e1g1").unwrap();
+        assert_eq!(game.history[0].1, "e1h1");
+        assert_eq!(game.machine.position.to_fen(), "r3k2r/pppppppp/8/8/8/8/PPPPPPPP/R4RK1 b ha - 1 1");
+        assert!(log.lock().unwrap().iter().any(|l| l == "position fen r3k2r/pppppppp/8/8/8/8/PPPPPPPP/R3K2R w HAha - 0 1 moves e1h1"));
+    }
+
+    #[test]
+    fn it_works14() {   // analysis of a position without moves needs no engine
+        let mut game = Game::dummy();
+        game.engine_path = String::from("/nonexistent/engine");
+        game.machine.position = Position::from_fen("rnb1kbnr/pppp1ppp/8/4p3/6Pq/5P2/PPPPP2P/RNBQKBNR w KQkq - 1 3").unwrap();
+        let lines = game.analyse(3).unwrap();
+        assert_eq!((lines.len(), lines[0].score, lines[0].info.depth), (1, SFScore::Mate(0), Some(0)));
+        game.machine.position = Position::from_fen("7k/5Q2/6K1/8/8/8/8/8 b - - 0 1").unwrap();
+        assert_eq!(game.analyse(1).unwrap()[0].score, SFScore::Centipawns(0));
+        assert_eq!(game.hint().unwrap(), None);
+    }
+
+    #[test]
+    fn it_works15() {   // games which end at the same time are both kept
+        let dir = std::env::temp_dir().join(format!("chess_firmware_pgn_{}", std::process::id()));
+        let mut game = Game::dummy();
+        game.resign(true).unwrap();
+        game.pgn_dir = Some(dir.to_string_lossy().to_string());
+        let paths: Vec<String> = (0..3).map(|_| game.save_pgn().unwrap()).collect();
+        assert!(paths[0] != paths[1] && paths[1] != paths[2] && paths[0] != paths[2]);
+        assert_eq!(std::fs::read_dir(&dir).unwrap().count(), 3);
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+}
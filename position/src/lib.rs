@@ -1,8 +1,8 @@
 pub mod position {
 
     use std::{collections::HashMap, num::ParseIntError, cmp::{min, max}};
-    use stockfish::{get_move, SFResults, SFErrors};
     use mctrl::motor::{Field, Speeds, FieldUsize, MotorInstructions, PosNow};
+    use crate::bitboard::{field, piece_index, square, squares, Bitboards};
 
 
     #[derive(Debug)]
@@ -19,27 +19,67 @@ pub mod position {
     }
 
     #[derive(Debug)]
+    #[derive(Clone, Copy, PartialEq, Eq)]
     pub enum DrawR {    // specifies reason for draw
-        Repetition, // unused, !todo!
+        Repetition, // threefold
+        FivefoldRepetition,
         FiftyMove,
-        Stalemate
+        Stalemate,
+        InsufficientMaterial,   // no sequence of legal moves can lead to mate
+        Agreement,  // draw offer was accepted
+        Adjudication    // engine game was ended early by the rules of the match
     }
 
     #[derive(Debug)]
+    #[derive(Clone, Copy, PartialEq, Eq)]
     pub enum State {    // specifies in which state the position is
         Normal,
         Mate(bool),
         Draw(DrawR),
-        Resign(bool)    // not implemented yet, !todo!
+        Resign(bool)    // one side resigned, true if white wins
     }
 
     #[derive(Debug)]
+    #[derive(Clone, Copy)]
     #[derive(Eq, Hash, PartialEq)]
     pub enum MoveType { // specifies type of move, important for pathfinding
         Normal(Piece),
         Capturing(Piece, Piece),
-        Rochade(Piece),
-        EnPassant((usize, usize))
+        Rochade(Piece, usize),  // side (king or queen), column of the rook
+        EnPassant((usize, usize)),
+        Promotion(Piece, Option<Piece>) // piece the pawn turns into, captured piece
+    }
+
+    #[derive(Debug)]
+    #[derive(Clone, Copy)]
+    #[derive(Eq, Hash, PartialEq)]
+    pub struct LegalMove {  // move found by the move generator, for rochade the start and end square of the king
+        pub ind_move: ((usize, usize), (usize, usize)),
+        pub mt: MoveType
+    }
+
+    impl LegalMove {
+        pub fn to_coordinates(&self) -> String {    // converts to coordinate notation, e.g. e2e4 or e7e8n, chess960 rochade as king takes own rook, e.g. b1a1
+            let promotion = match self.mt {
+                MoveType::Promotion(p, _) => p.piece_to_letter().to_lowercase(),
+                _ => String::new()
+            };
+            let to = match self.mt {
+                MoveType::Rochade(_, rook) if self.ind_move.0.1 != 7 || (rook != 3 && rook != 10) => (self.ind_move.0.0, rook),
+                _ => self.ind_move.1
+            };
+            format!("{}{}{}", index_to_coordinates(self.ind_move.0), index_to_coordinates(to), promotion)
+        }
+    }
+
+    fn step(field: (usize, usize), (dr, ds): (isize, isize)) -> Option<(usize, usize)> {  // next square in given direction, None if it leaves the board (sidebars don't count)
+        let r = field.0 as isize + dr;
+        let s = field.1 as isize + ds;
+        if (0..8).contains(&r) && (3..11).contains(&s) {
+            Some((r as usize, s as usize))
+        } else {
+            None
+        }
     }
 
     fn to_int(s: &str) -> i32 { // helper function, converts string into int, returns 0 if parse failed
@@ -101,7 +141,7 @@ pub mod position {
             }
         }
 
-        fn piece_to_color(&self) -> bool {  // return color, true => white, white if None
+        pub fn piece_to_color(&self) -> bool {  // return color, true => white, white if None
             match &self {
                 Piece::King(b) => *b,
                 Piece::Queen(b) => *b,
@@ -176,16 +216,38 @@ pub mod position {
 
     #[derive(Debug)]
     pub enum MoveError {    // possible conditions preventing a move
-        FenParse(ParseIntError),
         NoFigurStart,
         OwnFigurEnd,
         MoveNotFitPiece(Piece),
-        MoveParse(ParseIntError),
         WrongFigurStart,
         UnrightCoordinates,
         UnallowedRochade,
+        KingInCheck,
+        SanParse,   // text is no valid san
+        SanAmbiguous,   // san fits more than one legal move
+        SanNoMove,  // san fits no legal move
         ImpossiblePosition,
-        CleaningError(CleaningError)
+        CleaningError(CleaningError),
+        GameOver    // the game has a result already, see mainp::Game
+    }
+
+    #[derive(Debug)]
+    pub enum FenError { // reasons why a fen is rejected by from_fen()
+        FieldCount(usize),  // fen needs 6 fields, or 4 without the clocks
+        RankCount(usize),
+        RankLength(usize),  // rank (1..8) doesn't describe exactly 8 squares
+        UnknownPiece(char),
+        TooManyPieces(Piece),   // more pieces of a kind than the board has, e.g. a third knight
+        KingCount(bool),    // color without king
+        PawnOnBackRank,
+        SideToMove(String),
+        Rochade(String),    // unknown or repeated letters
+        RochadeMismatch(char),  // right without king and rook on their squares
+        EnPassant(String),  // no square behind a pawn which just moved two squares
+        HalfMove(ParseIntError),
+        FullMove(Option<ParseIntError>),    // None if the number is 0
+        OpponentInCheck,    // side which is not to move is in check
+        ImpossiblePosition  // pieces don't fit into the sidebars
     }
 
     #[derive(Debug)]
@@ -201,14 +263,20 @@ pub mod position {
         ImpossibleMove(MoveError),
         EnpassantMissing,
         CleaningError(CleaningError),
-        SFError(SFErrors),
         Other
     }
 
     #[derive(Debug)]
     pub enum CleaningError { // errors when moving piece off the board
-        KingBeforeQueen,
-        ImpossiblePosition
+        NoPiece,    // Piece::None can't be stored
+        StorageFull(Piece), // no empty square left in the sidebar of the pieces color
+        MissingPiece(Piece) // no spare piece for promotion in the sidebars
+    }
+
+    #[derive(Debug)]
+    pub enum SetupError {   // errors when arranging the pieces for a new position
+        MissingPiece(Piece),
+        NoFreeField
     }
 
     #[derive(Debug)]
@@ -217,6 +285,47 @@ pub mod position {
         Rochade(u32),
         Stuck
     }
+    #[derive(Debug)]
+    #[derive(Clone, Copy, PartialEq, Eq)]
+    pub struct StorageLayout {  // home piece of every square of the sidebars, white pieces are stored in columns 0..3, black ones in 11..14
+        pub slots: [[Piece; 6]; 8]  // columns 0, 1, 2, 11, 12, 13, Piece::None takes any piece of the sides color
+    }
+
+    impl StorageLayout {
+        pub fn new() -> Self {  // usual layout, officers next to their back rank, pawns on the other half, the inner columns are free
+            let mut slots = [[Piece::None; 6]; 8];
+            for (i, pce) in [Piece::Queen as fn(bool) -> Piece, Piece::Rook, Piece::Knight, Piece::Bishop].iter().enumerate() {
+                slots[7 - i][0] = pce(true);
+                slots[7 - i][1] = pce(true);
+                slots[i][5] = pce(false);
+                slots[i][4] = pce(false);
+            };
+            slots[7][2] = Piece::King(true);
+            slots[0][3] = Piece::King(false);
+            for r in 0..4 {
+                slots[r][0] = Piece::Pawn(true);
+                slots[r][1] = Piece::Pawn(true);
+                slots[7 - r][5] = Piece::Pawn(false);
+                slots[7 - r][4] = Piece::Pawn(false);
+            };
+            StorageLayout { slots }
+        }
+
+        pub fn home(&self, field: (usize, usize)) -> Piece {   // home piece of a square of the sidebars, Piece::None for the playing area
+            match field.1 {
+                0..3 => self.slots[field.0][field.1],
+                11..14 => self.slots[field.0][field.1 - 8],
+                _ => Piece::None
+            }
+        }
+    }
+
+    impl Default for StorageLayout {
+        fn default() -> Self {
+            Self::new()
+        }
+    }
+
     #[derive(Debug)]
     #[derive(Clone)]
     pub struct Position {   // stores all essential informations about current position of the board
@@ -225,12 +334,17 @@ pub mod position {
         pub moves: u32,
         pub en_passant: String,
         pub rochade: [Piece;4],
-        pub since_pawn_major: u32   // draw logic not implemented yet, !todo!
+        pub storage: StorageLayout, // where captured and spare pieces are put in the sidebars
+        pub rochade_rooks: [usize;4],   // columns of the rooks belonging to the rochade rights, differ from a and h only in chess960
+        pub chess960: bool, // fen gets shredder notation for the rochade rights
+        pub since_pawn_major: u32,  // half moves since the last capture or pawn move
+        pub hash: u64,  // zobrist hash of the board, side to move, rochade rights and en passant square, sidebars are ignored
+        pub bb: Bitboards   // playing area as bitboards, kept in sync with fields by set_field()
     }    
 
     impl Position {
         pub fn new_reset() -> Self {    // generator, resets position to starting position
-            Position {
+            let mut pos = Position {
                 colorw: true,
                 fields: [[Piece::None,Piece::None,Piece::None,Piece::Rook(false),Piece::Knight(false),Piece::Bishop(false),Piece::Queen(false),Piece::King(false),Piece::Bishop(false),Piece::Knight(false),Piece::Rook(false),Piece::None,Piece::None,Piece::Queen(false)],
                     [Piece::None,Piece::None,Piece::None,Piece::Pawn(false),Piece::Pawn(false),Piece::Pawn(false),Piece::Pawn(false),Piece::Pawn(false),Piece::Pawn(false),Piece::Pawn(false),Piece::Pawn(false),Piece::None,Piece::None,Piece::None],
@@ -243,8 +357,49 @@ pub mod position {
                 moves: 0,
                 en_passant: "-".to_string(),
                 rochade: [Piece::King(true),Piece::Queen(true),Piece::King(false),Piece::Queen(false)],
-                since_pawn_major: 0
-            }
+                storage: StorageLayout::new(),
+                rochade_rooks: [10, 3, 10, 3],
+                chess960: false,
+                since_pawn_major: 0,
+                hash: 0,
+                bb: Bitboards::new()
+            };
+            pos.bb = Bitboards::from_fields(&pos.fields);
+            pos.hash = pos.compute_hash();
+            pos
+        }
+
+        pub fn new_chess960(n: u16) -> Option<Self> {   // generator, chess960 starting position with the given number (0..960, scharnagl numbering, 518 is the usual one)
+            if n >= 960 {
+                return None
+            };
+            type PieceOf = fn(bool) -> Piece;   // piece without color, e.g. Piece::Rook
+            let mut row: [Option<PieceOf>; 8] = [None; 8];
+            let mut n = n as usize;
+            row[(n % 4) * 2 + 1] = Some(Piece::Bishop);  // light squared bishop on b, d, f or h
+            n /= 4;
+            row[(n % 4) * 2] = Some(Piece::Bishop);  // dark squared bishop on a, c, e or g
+            n /= 4;
+            let free = |row: &[Option<PieceOf>; 8]| (0..8).filter(|i| row[*i].is_none()).collect::<Vec<usize>>();
+            row[free(&row)[n % 6]] = Some(Piece::Queen);
+            n /= 6;
+            let (k1, k2) = [(0, 1), (0, 2), (0, 3), (0, 4), (1, 2), (1, 3), (1, 4), (2, 3), (2, 4), (3, 4)][n];
+            let empty = free(&row);
+            row[empty[k1]] = Some(Piece::Knight);
+            row[empty[k2]] = Some(Piece::Knight);
+            let rest = free(&row);  // rook, king, rook from left to right
+            row[rest[0]] = Some(Piece::Rook);
+            row[rest[1]] = Some(Piece::King);
+            row[rest[2]] = Some(Piece::Rook);
+            let mut pos = Position::new_reset();
+            for (i, pce) in row.iter().flatten().enumerate() {
+                pos.fields[7][i + 3] = pce(true);
+                pos.fields[0][i + 3] = pce(false);
+            };
+            pos.rochade_rooks = [rest[2] + 3, rest[0] + 3, rest[2] + 3, rest[0] + 3];
+            pos.chess960 = true;
+            pos.resync();
+            Some(pos)
         }
 
         pub fn print_out(&self) {   // prints piece-positions
@@ -293,10 +448,15 @@ pub mod position {
                 res.push_str("b ");
             }
     
-            // rochade-rights
+            // rochade-rights, in chess960 with the column of the rook (shredder fen)
             let mut rochade_res = String::new();
-            for piece in self.rochade {
-                rochade_res.push_str(&piece.piece_to_letter());
+            for (i, piece) in self.rochade.iter().enumerate() {
+                if self.chess960 && *piece != Piece::None {
+                    let file = (b'a' + self.rochade_rooks[i] as u8 - 3) as char;
+                    rochade_res.push(if piece.piece_to_color() { file.to_ascii_uppercase() } else { file });
+                } else {
+                    rochade_res.push_str(&piece.piece_to_letter());
+                }
             }
             if rochade_res == "".to_string() {
                 rochade_res.push_str("-");
@@ -306,8 +466,8 @@ pub mod position {
             // en_passant?
             res.push_str(&format!(" {}", self.en_passant));
     
-            // moves (50-moves-rule)
-            res.push_str(&format!(" {}", self.since_pawn_major / 2));
+            // half moves since last capture or pawn move (50-moves-rule)
+            res.push_str(&format!(" {}", self.since_pawn_major));
     
             // movenumber
             res.push_str(&format!(" {}", (self.moves / 2) + 1));
@@ -315,41 +475,37 @@ pub mod position {
             res
         }
 
-        pub fn from_fen(fen: &str) -> Result<Self, MoveError> { // generator, converts fen to position
+        pub fn from_fen(fen: &str) -> Result<Self, FenError> { // generator, converts fen to position, the clocks may be left out
             let fen: Vec<&str> = fen.split_ascii_whitespace().collect();
+            if fen.len() != 6 && fen.len() != 4 {
+                return Err(FenError::FieldCount(fen.len()))
+            };
             let colorw = match fen[1] {
                 "w" => true,
-                _ => false
+                "b" => false,
+                s => return Err(FenError::SideToMove(s.to_string()))
             };
-            let moves = match fen[5].to_string().parse::<u32>() {
-                Ok(num) => {
-                    if colorw {
-                        (num-1)*2
-                    } else {
-                        (num-1)*2 + 1
+            let (since_pawn_major, fullmove) = match (fen.get(4), fen.get(5)) {
+                (Some(h), Some(f)) => {
+                    let h = match h.parse::<u32>() {
+                        Ok(num) => num,
+                        Err(rr) => return Err(FenError::HalfMove(rr))
+                    };
+                    match f.parse::<u32>() {
+                        Ok(0) => return Err(FenError::FullMove(None)),
+                        Ok(num) => (h, num),
+                        Err(rr) => return Err(FenError::FullMove(Some(rr)))
                     }
                 },
-                Err(rr) => return Err(MoveError::FenParse(rr))
-            };
-            let en_passant = fen[3].to_string();
-            let mut rochade = [Piece::None; 4];
-            fen[2].chars().for_each(|l| {
-                match l {
-                    'K' => rochade[0] = Piece::King(true),
-                    'Q' => rochade[1] = Piece::Queen(true),
-                    'k' => rochade[2] = Piece::King(false),
-                    'q' => rochade[3] = Piece::Queen(false),
-                    _ => {}
-                }
-            });
-            let since_pawn_major = match fen[4].parse::<u32>() {
-                Ok(num) => num,
-                Err(rr) => return Err(MoveError::FenParse(rr))
+                _ => (0, 1)
             };
+            let moves = if colorw { (fullmove - 1) * 2 } else { (fullmove - 1) * 2 + 1 };
+
+            // piece placement
             let mut all_pieces: HashMap<Piece, u8> = HashMap::new();
             for v in [true, false] {
                 all_pieces.insert(Piece::King(v), 1);
-                all_pieces.insert(Piece::Queen(v), 1);
+                all_pieces.insert(Piece::Queen(v), 2);
                 all_pieces.insert(Piece::Rook(v), 2);
                 all_pieces.insert(Piece::Knight(v), 2);
                 all_pieces.insert(Piece::Bishop(v), 2);
@@ -357,165 +513,215 @@ pub mod position {
             };
             let mut fields = [[Piece::None; 14]; 8];
             let rows: Vec<&str> = fen[0].split("/").collect();
-            let mut i = 0;
-            for row in rows {
-                let row: Vec<char> = row.chars().collect();
+            if rows.len() != 8 {
+                return Err(FenError::RankCount(rows.len()))
+            };
+            for (i, row) in rows.iter().enumerate() {
                 let mut j = 3;
-                for chr in row {
+                for chr in row.chars() {
                     if let Some(pce) = Piece::from_char(chr) {
-                        *(all_pieces.entry(pce).or_insert(0)) -= 1;
+                        if j >= 11 {
+                            return Err(FenError::RankLength(8 - i))
+                        };
+                        match all_pieces.get_mut(&pce) {
+                            Some(0) | None => return Err(FenError::TooManyPieces(pce)),
+                            Some(n) => *n -= 1
+                        };
+                        if matches!(pce, Piece::Pawn(_)) && (i == 0 || i == 7) {
+                            return Err(FenError::PawnOnBackRank)
+                        };
                         fields[i][j] = pce;
                         j += 1;
                     } else {
-                        match chr.to_string().parse::<usize>() {
-                            Ok(num) => j += num,
-                            Err(rr) => return Err(MoveError::FenParse(rr))
+                        match chr.to_digit(10) {
+                            Some(num @ 1..=8) => j += num as usize,
+                            _ => return Err(FenError::UnknownPiece(chr))
                         };
                     }
                 };
-                i += 1;
-            }
-            let mut pos = Position { colorw, fields, moves, en_passant, rochade, since_pawn_major};
-            let mut scnd_run = all_pieces.clone();
-            let mut rerun = false;
-            for (k, v) in all_pieces.iter() {
-                for _ in 0..*v {
-                    match pos.add_rest(k.clone()) {
-                        Ok(_) => *(scnd_run.entry(*k).or_insert(*v)) -= 1,
-                        Err(_) => rerun = true,
-                    }
+                if j != 11 {
+                    return Err(FenError::RankLength(8 - i))
                 };
             };
-            if rerun {
-                for (k, v) in scnd_run.iter() {
-                    for _ in 0..*v {
-                        match pos.add_rest(k.clone()) {
-                            Ok(_) => {},
-                            Err(_) => return Err(MoveError::ImpossiblePosition)
-                        }
+            for col in [true, false] {
+                if all_pieces[&Piece::King(col)] != 0 {
+                    return Err(FenError::KingCount(col))
+                };
+            };
+
+            // rochade rights, kq (x-fen, outermost rook) or the column of the rook (shredder fen), king and rook have to be on their home row
+            let mut rochade = [Piece::None; 4];
+            let mut rochade_rooks = [10, 3, 10, 3];
+            let mut chess960 = false;
+            if fen[2] != "-" {
+                for l in fen[2].chars() {
+                    let col = l.is_ascii_uppercase();
+                    let row = if col { 7 } else { 0 };
+                    let king = match (3..11).find(|s| fields[row][*s] == Piece::King(col)) {
+                        Some(k) => k,
+                        None => return Err(FenError::RochadeMismatch(l))
+                    };
+                    let is_rook = |s: &usize| fields[row][*s] == Piece::Rook(col);
+                    let (king_side, rook_col) = match l.to_ascii_uppercase() {
+                        'K' => (true, (king + 1..11).rev().find(is_rook)),
+                        'Q' => (false, (3..king).find(is_rook)),
+                        c @ 'A'..='H' => {
+                            chess960 = true;
+                            let s = c as usize - 'A' as usize + 3;
+                            (s > king, Some(s).filter(is_rook))
+                        },
+                        _ => return Err(FenError::Rochade(fen[2].to_string()))
+                    };
+                    let rook_col = match rook_col {
+                        Some(s) => s,
+                        None => return Err(FenError::RochadeMismatch(l))
+                    };
+                    let ind = if col { 0 } else { 2 } + if king_side { 0 } else { 1 };
+                    if rochade[ind] != Piece::None {
+                        return Err(FenError::Rochade(fen[2].to_string()))
+                    };
+                    if king != 7 || rook_col != if king_side { 10 } else { 3 } {
+                        chess960 = true;
+                    };
+                    rochade[ind] = if king_side { Piece::King(col) } else { Piece::Queen(col) };
+                    rochade_rooks[ind] = rook_col;
+                }
+            };
+
+            // en passant square has to be behind a pawn which just moved two squares
+            let en_passant = fen[3].to_string();
+            if en_passant != "-" {
+                let (r, s) = match coordinates_to_index(&en_passant) {
+                    Ok(ind) => ind,
+                    Err(_) => return Err(FenError::EnPassant(en_passant))
+                };
+                let (ep_row, pawn_row, from_row) = if colorw { (2, 3, 1) } else { (5, 4, 6) };
+                if r != ep_row || fields[pawn_row][s] != Piece::Pawn(!colorw) || fields[r][s] != Piece::None || fields[from_row][s] != Piece::None {
+                    return Err(FenError::EnPassant(en_passant))
+                };
+            };
+
+            let mut pos = Position { colorw, fields, moves, en_passant, rochade, storage: StorageLayout::new(), rochade_rooks, chess960, since_pawn_major, hash: 0, bb: Bitboards::from_fields(&fields) };
+            if pos.in_check(!colorw) {
+                return Err(FenError::OpponentInCheck)
+            };
+            pos.hash = pos.compute_hash();
+            // pieces which are not on the board go to the sidebars, outer squares first and in a fixed order so that the result doesn't depend on the hashmap
+            for col in [true, false] {
+                for pce in [Piece::King(col), Piece::Queen(col), Piece::Rook(col), Piece::Bishop(col), Piece::Knight(col), Piece::Pawn(col)] {
+                    for _ in 0..all_pieces[&pce] {
+                        if pos.add_rest(pce, if col { (7, 0) } else { (0, 13) }).is_err() {
+                            return Err(FenError::ImpossiblePosition)
+                        };
                     }
                 }
-            }
-            pos.add_rest(Piece::Queen(true)).unwrap();
-            pos.add_rest(Piece::Queen(false)).unwrap();
+            };
             Ok(pos)
 
         }
 
-        pub fn add_rest(&mut self, pce: Piece) -> Result<(usize, usize), CleaningError> {   // adds captured piece to sidebars
-            match pce {
-                Piece::Queen(true) => {
-                    if self.field_is_empty((7, 0)) {
-                        self.fields[7][0] = Piece::Queen(true);
-                        Ok((7, 0))
-                    } else {
-                        self.fields[7][1] = Piece::Queen(true);
-                        Ok((7, 1))
-                    }
-                },
-                Piece::Queen(false) => {
-                    if self.field_is_empty((0, 13)) {
-                        self.fields[0][13] = Piece::Queen(true);
-                        Ok((0, 13))
-                    } else {
-                        self.fields[0][12] = Piece::Queen(true);
-                        Ok((0, 12))
-                    }
-                },
-                Piece::King(true) => {
-                    self.fields[7][2] = Piece::King(true);
-                    Ok((7, 2))
-                },
-                Piece::King(false) => {
-                    self.fields[0][11] = Piece::King(true);
-                    Ok((0, 11))
-                },
-                Piece::Rook(true) => {
-                    let (r, s) = (6, 0);
-                    if self.field_is_empty((r, s)) {
-                        self.fields[r][s] = Piece::Rook(true);
-                        Ok((r, s))
-                    } else {
-                        self.fields[r][s+1] = Piece::Rook(true);
-                        Ok((r, s+1))
-                    }
-                },
-                Piece::Rook(false) => {
-                    let (r, s) = (1, 13);
-                    if self.field_is_empty((r, s)) {
-                        self.fields[r][s] = Piece::Rook(false);
-                        Ok((r, s))
-                    } else {
-                        self.fields[r][s-1] = Piece::Rook(false);
-                        Ok((r, s-1))
-                    }
-                },
-                Piece::Knight(true) => {
-                    let (r, s) = (5, 0);
-                    if self.field_is_empty((r, s)) {
-                        self.fields[r][s] = Piece::Knight(true);
-                        Ok((r, s))
-                    } else {
-                        self.fields[r][s+1] = Piece::Knight(true);
-                        Ok((r, s+1))
-                    }
-                },
-                Piece::Knight(false) => {
-                    let (r, s) = (2, 13);
-                    if self.field_is_empty((r, s)) {
-                        self.fields[r][s] = Piece::Knight(false);
-                        Ok((r, s))
-                    } else {
-                        self.fields[r][s-1] = Piece::Knight(false);
-                        Ok((r, s-1))
-                    }
-                },
-                Piece::Bishop(true) => {
-                    let (r, s) = (4, 0);
-                    if self.field_is_empty((r, s)) {
-                        self.fields[r][s] = Piece::Bishop(true);
-                        Ok((r, s))
-                    } else {
-                        self.fields[r][s+1] = Piece::Bishop(true);
-                        Ok((r, s+1))
-                    }
-                },
-                Piece::Bishop(false) => {
-                    let (r, s) = (3, 13);
-                    if self.field_is_empty((r, s)) {
-                        self.fields[r][s] = Piece::Bishop(false);
-                        Ok((r, s))
-                    } else {
-                        self.fields[r][s-1] = Piece::Bishop(false);
-                        Ok((r, s-1))
-                    }
+        pub fn add_rest(&mut self, pce: Piece, near: (usize, usize)) -> Result<(usize, usize), CleaningError> {   // puts a piece into the nearest free slot of the sidebars, see storage_slot()
+            if pce == Piece::None {
+                return Err(CleaningError::NoPiece)
+            };
+            match self.storage_slot(pce, near, &[]) {
+                Some(f) => {
+                    self.fields[f.0][f.1] = pce;
+                    Ok(f)
                 },
-                Piece::Pawn(true) => {
-                    for r in 0..4 {
-                        for s in 0..2 {
-                            if self.field_is_empty((r, s)) {
-                                self.fields[r][s] = Piece::Pawn(true);
-                                return Ok((r, s))
-                            }
-                        }
-                    };
-                    Err(CleaningError::ImpossiblePosition)
+                None => Err(CleaningError::StorageFull(pce))
+            }
+        }
+
+        pub fn take_rest(&mut self, pce: Piece, near: (usize, usize)) -> Result<(usize, usize), CleaningError> {   // removes a piece of the given type from the sidebars, e.g. for a promotion, gives back its square
+            match self.find_rest(pce, near) {
+                Some(f) => {
+                    self.fields[f.0][f.1] = Piece::None;
+                    Ok(f)
                 },
-                Piece::Pawn(false) => {
-                    for r in 0..4 {
-                        for s in 0..2 {
-                            if self.field_is_empty((7-r, 13-s)) {
-                                self.fields[7-r][13-s] = Piece::Pawn(false);
-                                return Ok((7-r, 13-s))
-                            }
-                        }
+                None => Err(CleaningError::MissingPiece(pce))
+            }
+        }
+
+        // empty square in the sidebar of the pieces color nearest to the given square, the home squares of the piece type come first,
+        // then the free squares of the layout and at last the home squares of other types, reserved squares are skipped
+        pub fn storage_slot(&self, pce: Piece, near: (usize, usize), reserved: &[(usize, usize)]) -> Option<(usize, usize)> {
+            let cols = if pce.piece_to_color() { 0..3 } else { 11..14 };
+            fields_in(cols)
+                .filter(|f| self.field_is_empty(*f) && !reserved.contains(f))
+                .min_by_key(|f| {
+                    let rank = match self.storage.home(*f) {
+                        h if h == pce => 0,
+                        Piece::None => 1,
+                        _ => 2
                     };
-                    Err(CleaningError::ImpossiblePosition)
+                    (rank, field_dist(*f, near), f.0.abs_diff(near.0) + f.1.abs_diff(near.1))
+                })
+        }
+
+        pub fn inventory(&self) -> HashMap<Piece, u8> {    // number of pieces of every type in the sidebars
+            let mut res = HashMap::new();
+            for f in fields_in((0..3).chain(11..14)) {
+                if self.fields[f.0][f.1] != Piece::None {
+                    *res.entry(self.fields[f.0][f.1]).or_insert(0) += 1;
+                }
+            };
+            res
+        }
+
+        fn set_field(&mut self, field: (usize, usize), pce: Piece) {    // writes a square and keeps the hash and the bitboards up to date
+            if (3..11).contains(&field.1) {
+                let old = self.fields[field.0][field.1];
+                self.hash ^= zobrist_piece(old, field) ^ zobrist_piece(pce, field);
+                self.bb.remove(square(field), old);
+                self.bb.put(square(field), pce);
+            };
+            self.fields[field.0][field.1] = pce;
+        }
+
+        pub fn resync(&mut self) {  // recalculates hash and bitboards, needed after writing fields directly
+            self.bb = Bitboards::from_fields(&self.fields);
+            self.hash = self.compute_hash();
+        }
+
+        fn state_hash(&self) -> u64 {   // part of the hash which doesn't depend on the pieces
+            let mut res = if self.colorw { ZOBRIST.colorw } else { 0 };
+            for (i, right) in self.rochade.iter().enumerate() {
+                if *right != Piece::None {
+                    res ^= ZOBRIST.rochade[i];
                 }
-                _ => return Err(CleaningError::KingBeforeQueen)
+            };
+            if let Ok(f) = coordinates_to_index(&self.en_passant) {
+                res ^= ZOBRIST.en_passant[f.1 - 3];
+            };
+            res
+        }
+
+        pub fn compute_hash(&self) -> u64 { // calculates the hash from scratch
+            let mut res = self.state_hash();
+            for f in fields_in(3..11) {
+                res ^= zobrist_piece(self.fields[f.0][f.1], f);
+            };
+            res
+        }
+
+        pub fn repetition_key(&self) -> u64 {   // hash for comparing positions after fide rules, en passant only counts if the capture is possible
+            match coordinates_to_index(&self.en_passant) {
+                Ok(f) if !self.legal_moves().iter().any(|m| matches!(m.mt, MoveType::EnPassant(_))) => self.hash ^ ZOBRIST.en_passant[f.1 - 3],
+                _ => self.hash
             }
         }
 
+        pub fn find_rest(&self, pce: Piece, near: (usize, usize)) -> Option<(usize, usize)> {  // finds the given piece in the sidebars, nearest to the given square
+            self.find_rest_except(pce, near, &[])
+        }
+
+        fn find_rest_except(&self, pce: Piece, near: (usize, usize), taken: &[(usize, usize)]) -> Option<(usize, usize)> {
+            fields_in((0..3).chain(11..14))
+                .filter(|f| self.fields[f.0][f.1] == pce && !taken.contains(f))
+                .min_by_key(|f| field_dist(*f, near))
+        }
+
         pub fn field_is_empty(&self, field: (usize, usize)) -> bool {   // checks if field contains a piece
             if self.fields[field.0][field.1] == Piece::None {
                 true
@@ -524,231 +730,544 @@ pub mod position {
             }
         }
 
-        pub fn update(&mut self, ind_move: ((usize, usize), (usize, usize)), coord_move: &str, elo: u32, time: u32) -> Result<(State, Vec<PFIType>, Position), UpdateError> {
+        pub fn update(&mut self, ind_move: ((usize, usize), (usize, usize)), coord_move: &str) -> Result<(State, Vec<PFIType>, Position), UpdateError> {
             let cppos = self.clone(); 
-            let mt = match self.validate_move_possibility(coord_move) {
+            let legal = match self.legal_move(coord_move) {
                 Err(rr) => return Err(UpdateError::ImpossibleMove(rr)),
-                Ok(mt) => mt
+                Ok(mv) => mv
             };
-            let ind_move    = ind_move;
+            match self.perform(&legal, ind_move) {
+                Ok(moves) => Ok((self.game_state(), moves, cppos)),  // state of the game after the move, moves for pieces on the board, for pathfinding, position before move
+                Err(rr) => {
+                    *self = cppos;  // nothing of a failed move stays, e.g. the side to move
+                    Err(rr)
+                }
+            }
+        }
+
+        fn perform(&mut self, legal: &LegalMove, ind_move: ((usize, usize), (usize, usize))) -> Result<Vec<PFIType>, UpdateError> {   // changes board and sidebars for update(), gives back the moves for pathfinding
+            let mt = legal.mt;
             let piece = self.index_to_piece(ind_move.0).unwrap(); //existence already checked at validate_move_possibility
-            // inverting color
-            println!("{:?}", piece);
-            self.colorw = !self.colorw;
-            //adding 1 move 
-            self.moves += 1;
-            // writing enpassant string, adjusting rochade and since_pawn_major
-            self.since_pawn_major += 1;
-            match piece {
-                Piece::King(c) => {
-                    if c {
-                        self.rochade[0] = Piece::None;
-                        self.rochade[1] = Piece::None;
-                    } else {
-                        self.rochade[2] = Piece::None;
-                        self.rochade[3] = Piece::None;
-                    }
-                },
-                Piece::Rook(_) => {
-                    match ind_move.0 {
-                        (0, 3) => self.rochade[3] = Piece::None,
-                        (0,10) => self.rochade[2] = Piece::None,
-                        (7, 3) => self.rochade[1] = Piece::None,
-                        (7, 10) => self.rochade[0] = Piece::None,
-                        _ => {}
-                    }
-                },
-                Piece::Pawn(c) => {
-                    self.since_pawn_major = 0;
-                    self.en_passant = "-".to_string();
-                    if c {
-                        if ind_move.0.0 == 6 && ind_move.1.0 == 4 {
-                            self.en_passant = format!("{}3", coord_move.chars().nth(0).unwrap());
-                        };
-                    } else {
-                        if ind_move.0.0 == 1 && ind_move.1.0 == 3 {
-                            self.en_passant = format!("{}6", coord_move.chars().nth(0).unwrap());
-                        };
-                    }
-                },
-                _ => {}
+            if let MoveType::Promotion(np, _) = mt {
+                if self.find_rest(np, ind_move.1).is_none() {
+                    return Err(UpdateError::CleaningError(CleaningError::MissingPiece(np)))
+                }
             };
+            println!("{:?}", piece);
+            self.advance_state(piece, ind_move);
             //update fields (and since_pawn_major)
             let mut moves: Vec<PFIType> = Vec::new();
             let ((sfr, sfs), (efr, efs)) = ind_move;
             match mt {  // moving piece, 
                 MoveType::Normal(p) => {
-                    self.fields[efr][efs] = p;
-                    self.fields[sfr][sfs] = Piece::None;
-                    match (p, efr) {
-                        (Piece::Knight(_), _) => moves.push(PFIType::Custom(ind_move.0, ind_move.1)),
-                        (Piece::Pawn(col), 0 | 7) => {
-                            self.fields[efr][efs] = Piece::Queen(col);
-                            let rest_ind = match self.add_rest(Piece::Pawn(col)) {
-                                Ok(t) => t,
-                                Err(rr) => return Err(UpdateError::CleaningError(rr))
-                            };
-                            moves.push(PFIType::Custom(ind_move.0, rest_ind));
-                            let queen_ind = match col {
-                                true => if self.index_to_piece((7, 1)) == Some(Piece::Queen(true)) { (7, 1) } else { (7, 0) },
-                                false => if self.index_to_piece((0, 12)) == Some(Piece::Queen(false)) { (0, 12) } else { (0, 13) }
-                            };
-                            moves.push(PFIType::Custom(queen_ind, ind_move.1));
-                        }
+                    self.set_field((efr, efs), p);
+                    self.set_field((sfr, sfs), Piece::None);
+                    match p {
+                        Piece::Knight(_) => moves.push(PFIType::Custom(ind_move.0, ind_move.1)),
                         _ => moves.push(PFIType::NMove(ind_move.0, ind_move.1))
                     }
                 },
                 MoveType::Capturing(p, cp) => {
                     self.since_pawn_major = 0;
-                    let rest_ind = match self.add_rest(cp) {
+                    let rest_ind = match self.add_rest(cp, ind_move.1) {
                         Ok(t) => t,
                         Err(rr) => return Err(UpdateError::CleaningError(rr))
                     };
                     moves.push(PFIType::Custom(ind_move.1, rest_ind));
-                    self.fields[sfr][sfs] = Piece::None;
-                    match (p,  efr){
-                        (Piece::Pawn(col), 0 | 7) => {
-                            self.fields[efr][efs] = Piece::Queen(col);
-                            let rest_ind2 = match self.add_rest(Piece::Pawn(col)) {
-                                Ok(t) => t,
-                                Err(rr) => return Err(UpdateError::CleaningError(rr))
-                            };
-                            moves.push(PFIType::Custom(ind_move.0, rest_ind2));
-                            let queen_ind = match col {
-                                true => if self.index_to_piece((7, 1)) == Some(Piece::Queen(true)) { (7, 1) } else { (7, 0) },
-                                false => if self.index_to_piece((0, 12)) == Some(Piece::Queen(false)) { (0, 12) } else { (0, 13) }
-                            };
-                            moves.push(PFIType::Custom(queen_ind, ind_move.1));
-                        },
-                        _ => {
-                            self.fields[efr][efs] = p;
-                            moves.push(PFIType::NMove(ind_move.0, ind_move.1));
-                        }
-                    }
+                    self.set_field((sfr, sfs), Piece::None);
+                    self.set_field((efr, efs), p);
+                    moves.push(PFIType::NMove(ind_move.0, ind_move.1));
+                },
+                MoveType::Promotion(np, cp) => {    // captured piece goes to the sidebars, the new piece is taken from there, then the pawn leaves
+                    if let Some(cp) = cp {
+                        let rest_ind = match self.add_rest(cp, ind_move.1) {
+                            Ok(t) => t,
+                            Err(rr) => return Err(UpdateError::CleaningError(rr))
+                        };
+                        moves.push(PFIType::Custom(ind_move.1, rest_ind));
+                    };
+                    let spare = match self.take_rest(np, ind_move.1) {
+                        Ok(f) => f,
+                        Err(rr) => return Err(UpdateError::CleaningError(rr))
+                    };
+                    moves.push(PFIType::Custom(spare, ind_move.1));
+                    let rest_ind = match self.add_rest(piece, ind_move.0) {
+                        Ok(t) => t,
+                        Err(rr) => return Err(UpdateError::CleaningError(rr))
+                    };
+                    moves.push(PFIType::Custom(ind_move.0, rest_ind));
+                    self.set_field((sfr, sfs), Piece::None);
+                    self.set_field((efr, efs), np);
                 },
                 MoveType::EnPassant(bind) => {
                     let beaten_piece = match self.index_to_piece(bind) {
                         Some(i) => i,
                         None => return Err(UpdateError::EnpassantMissing)
                     };
-                    let rest_ind = match self.add_rest(beaten_piece) {
+                    let rest_ind = match self.add_rest(beaten_piece, bind) {
                         Ok(t) => t,
                         Err(rr) => return Err(UpdateError::CleaningError(rr))
                     };
                     moves.push(PFIType::Custom(bind, rest_ind));
-                    self.fields[bind.0][bind.1] = Piece::None;
-                    self.fields[sfr][sfs] = Piece::None;
-                    self.fields[efr][efs] = Piece::Pawn(!beaten_piece.piece_to_color());
+                    self.set_field(bind, Piece::None);
+                    self.set_field((sfr, sfs), Piece::None);
+                    self.set_field((efr, efs), Piece::Pawn(!beaten_piece.piece_to_color()));
                     moves.push(PFIType::NMove(ind_move.0, ind_move.1));
                 },
-                MoveType::Rochade(p) => {
-                    let (ks, ke, rs, re) = match p {
-                        Piece::King(true) => (coordinates_to_index("e1").unwrap(), coordinates_to_index("g1").unwrap(), coordinates_to_index("h1").unwrap(), coordinates_to_index("f1").unwrap()),
-                        Piece::Queen(true) => (coordinates_to_index("e1").unwrap(), coordinates_to_index("c1").unwrap(), coordinates_to_index("a1").unwrap(), coordinates_to_index("d1").unwrap()),
-                        Piece::King(false) => (coordinates_to_index("e8").unwrap(), coordinates_to_index("g8").unwrap(), coordinates_to_index("h8").unwrap(), coordinates_to_index("f8").unwrap()),
-                        Piece::Queen(false) => (coordinates_to_index("e8").unwrap(), coordinates_to_index("c8").unwrap(), coordinates_to_index("a8").unwrap(), coordinates_to_index("d8").unwrap()),
-                        _ => return Err(UpdateError::Other)
-                    };
-                    self.fields[ks.0][ks.1] = Piece::None;
-                    self.fields[ke.0][ke.1] = Piece::King(p.piece_to_color());
-                    self.fields[rs.0][rs.1] = Piece::None;
-                    self.fields[re.0][re.1] = Piece::Rook(p.piece_to_color());
-                    moves.push(PFIType::Rochade(p, [ks, ke, rs, re]));
-                }
-            };
-            let mut state = match get_move(&self.to_fen(), elo, time) {
-                Err(rr) => return Err(UpdateError::SFError(rr)),
-                Ok(s) => {
-                    match s {
-                        SFResults::Normal(_) => State::Normal,
-                        SFResults::Stalemate => State::Draw(DrawR::Stalemate),
-                        SFResults::Mate => State::Mate(!self.colorw)
+                MoveType::Rochade(p, rook) => {
+                    let (ks, ke) = legal.ind_move;
+                    let (rs, re) = ((ks.0, rook), (ks.0, if let Piece::King(_) = p { 8 } else { 6 }));
+                    let mut grid = self.fields;
+                    self.apply_board(legal);
+                    if ks.1 == 7 && (rook == 3 || rook == 10) {
+                        moves.push(PFIType::Rochade(p, [ks, ke, rs, re]));
+                    } else {    // chess960, squares of king and rook may overlap, the planner parks a piece if they block each other
+                        let pending = [(ks, ke), (rs, re)].into_iter().filter(|(s, e)| s != e).collect();
+                        match order_moves(&mut grid, pending) {
+                            Ok(mut m) => moves.append(&mut m),
+                            Err(_) => return Err(UpdateError::Other)
+                        };
                     }
                 }
             };
-            if self.since_pawn_major > 49 {
-                state = State::Draw(DrawR::FiftyMove);
-            };
-            Ok((state, moves, cppos))   // state of the game after the move, moves for pieces on the board, for pathfinding, position before move
+            Ok(moves)
         }
 
-        pub fn validate_move_possibility(&self, cmove: &str) -> Result<MoveType, MoveError> {
-            match cmove {
-                "e1g1" => {
-                    match self.rochade[0] {
-                        Piece::King(true) => return Ok(MoveType::Rochade(Piece::King(true))),
-                        _ => return Err(MoveError::UnallowedRochade)
-                    }
-                },
-                "e1c1" => {
-                    match self.rochade[1] {
-                        Piece::Queen(true) => return Ok(MoveType::Rochade(Piece::Queen(true))),
-                        _ => return Err(MoveError::UnallowedRochade)
+        fn advance_state(&mut self, piece: Piece, ind_move: ((usize, usize), (usize, usize))) {    // side to move, counters, en passant and rochade rights after a move of the piece, keeps the hash up to date
+            // inverting color
+            self.hash ^= self.state_hash();
+            self.colorw = !self.colorw;
+            //adding 1 move 
+            self.moves += 1;
+            // writing enpassant string, adjusting rochade and since_pawn_major
+            self.since_pawn_major += 1;
+            self.en_passant = "-".to_string();
+            match piece {
+                Piece::King(c) => {
+                    if c {
+                        self.rochade[0] = Piece::None;
+                        self.rochade[1] = Piece::None;
+                    } else {
+                        self.rochade[2] = Piece::None;
+                        self.rochade[3] = Piece::None;
                     }
                 },
-                "e8g8" => {
-                    match self.rochade[2] {
-                        Piece::King(false) => return Ok(MoveType::Rochade(Piece::King(false))),
-                        _ => return Err(MoveError::UnallowedRochade)
+                Piece::Rook(_) => {
+                    for i in 0..4 {
+                        if ind_move.0 == (if i < 2 { 7 } else { 0 }, self.rochade_rooks[i]) {
+                            self.rochade[i] = Piece::None;
+                        }
                     }
                 },
-                "e8c8" => {
-                    match self.rochade[3] {
-                        Piece::Queen(false) => return Ok(MoveType::Rochade(Piece::Queen(false))),
-                        _ => return Err(MoveError::UnallowedRochade)
+                Piece::Pawn(c) => {
+                    self.since_pawn_major = 0;
+                    if c {
+                        if ind_move.0.0 == 6 && ind_move.1.0 == 4 {
+                            self.en_passant = index_to_coordinates((5, ind_move.0.1));
+                        };
+                    } else {
+                        if ind_move.0.0 == 1 && ind_move.1.0 == 3 {
+                            self.en_passant = index_to_coordinates((2, ind_move.0.1));
+                        };
                     }
                 },
                 _ => {}
             };
-            let (start_field, end_field) = cmove.split_at(2);
-            let piece = self.coordinates_to_piece(start_field)?;
-            if let Some(piece) = piece {
-                match (self.en_passant.as_str(), piece) {
-                    ("-",  _) => {},
-                    (mm, Piece::Pawn(_)) => {
-                        if mm == cmove.split_at(2).1 {
-                            let mut ind_mm = coordinates_to_index(mm)?;
-                            if ind_mm.0 == 2 {
-                                ind_mm.0 += 1
-                            } else {
-                                ind_mm.0 -= 1
-                            };
-                            return Ok(MoveType::EnPassant(ind_mm))
-                        }
-                    },
-                    (_, _) => {}
-                }
-                if piece == Piece::None {
-                    return Err(MoveError::NoFigurStart);
-                } else {
-                    if self.colorw ^ piece.piece_to_color() {
-                        return Err(MoveError::WrongFigurStart)
-                    }
-                };
-                match &piece.check_field(start_field, end_field) {
-                    true => {},
-                    false => return Err(MoveError::MoveNotFitPiece(piece))
-                };
-                let capt_piece = self.coordinates_to_piece(end_field)?;
-                if let Some(capt_piece) = capt_piece {
-                    if piece.piece_to_color() == capt_piece.piece_to_color() {
-                        return Err(MoveError::OwnFigurEnd)
-                    };
-                    Ok(MoveType::Capturing(piece, capt_piece))
-                } else {
-                    Ok(MoveType::Normal(piece))
+            for i in 0..4 {  // rook captured on its starting square
+                if ind_move.1 == (if i < 2 { 7 } else { 0 }, self.rochade_rooks[i]) {
+                    self.rochade[i] = Piece::None;
                 }
-            } else {
-                return Err(MoveError::NoFigurStart)
-            }            
+            };
+            self.hash ^= self.state_hash();
         }
 
-        pub fn coordinates_to_piece(&self, coord: &str) -> Result<Option<Piece>, MoveError> { // returns Piece from given square coordinates
-            let res = coordinates_to_index(coord)?;
-            Ok(self.index_to_piece(res))
+        pub fn make_move(&self, mv: &LegalMove) -> Position {  // performs a legal move without touching the sidebars, used for perft and searching
+            let mut res = self.clone();
+            res.advance_state(self.fields[mv.ind_move.0.0][mv.ind_move.0.1], mv.ind_move);
+            if matches!(mv.mt, MoveType::Capturing(_, _) | MoveType::EnPassant(_) | MoveType::Promotion(_, Some(_))) {
+                res.since_pawn_major = 0;
+            };
+            res.apply_board(mv);
+            res
         }
 
-        pub fn index_to_piece(&self, ind: (usize, usize)) -> Option<Piece> {    // returns Piece from given square index
+        pub fn perft(&self, depth: u32) -> u64 {   // number of leaf nodes of the legal move tree with the given depth
+            if depth == 0 {
+                return 1
+            };
+            let moves = self.legal_moves();
+            if depth == 1 {
+                return moves.len() as u64
+            };
+            moves.iter().map(|mv| self.make_move(mv).perft(depth - 1)).sum()
+        }
+
+        pub fn divide(&self, depth: u32) -> Vec<(String, u64)> {  // perft split up by the first move, for finding differences to other move generators
+            self.legal_moves().iter().map(|mv| (mv.to_coordinates(), self.make_move(mv).perft(depth.saturating_sub(1)))).collect()
+        }
+
+        pub fn game_state(&self) -> State { // determines the state of the game for the side to move
+            if self.legal_moves().is_empty() {
+                if self.in_check(self.colorw) {
+                    State::Mate(!self.colorw)
+                } else {
+                    State::Draw(DrawR::Stalemate)
+                }
+            } else if self.insufficient_material() {
+                State::Draw(DrawR::InsufficientMaterial)
+            } else if self.since_pawn_major >= 100 {
+                State::Draw(DrawR::FiftyMove)
+            } else {
+                State::Normal
+            }
+        }
+
+        pub fn insufficient_material(&self) -> bool {  // K vs K, K + minor piece vs K and bishops on squares of one color only, sidebars are ignored
+            const DARK: u64 = 0xAA55_AA55_AA55_AA55;    // a8 is a light square
+            let bb = &self.bb;
+            if [true, false].iter().any(|c| bb.piece(Piece::Queen(*c)) | bb.piece(Piece::Rook(*c)) | bb.piece(Piece::Pawn(*c)) != 0) {
+                return false
+            };
+            let knights = bb.piece(Piece::Knight(true)) | bb.piece(Piece::Knight(false));
+            let bishops = bb.piece(Piece::Bishop(true)) | bb.piece(Piece::Bishop(false));
+            (knights | bishops).count_ones() <= 1 || (knights == 0 && (bishops & DARK == 0 || bishops & !DARK == 0))
+        }
+
+        pub fn validate_move_possibility(&self, cmove: &str) -> Result<MoveType, MoveError> {  // checks the move against the legal moves of the position
+            Ok(self.legal_move(cmove)?.mt)
+        }
+
+        pub fn legal_move(&self, cmove: &str) -> Result<LegalMove, MoveError> {  // legal move described by the coordinates, rochade also as king takes own rook (chess960)
+            if cmove.len() > 5 {
+                return Err(MoveError::UnrightCoordinates)
+            };
+            let ind_move = ctim(cmove)?;
+            let piece = match self.index_to_piece(ind_move.0) {
+                Some(p) => p,
+                None => return Err(MoveError::NoFigurStart)
+            };
+            if self.colorw ^ piece.piece_to_color() {
+                return Err(MoveError::WrongFigurStart)
+            };
+            let letter = cmove.len() > 4;   // only promotions may have a fifth character
+            if matches!(piece, Piece::King(_)) && self.index_to_piece(ind_move.1) == Some(Piece::Rook(self.colorw)) {
+                if let Some(mv) = self.moves_from(ind_move.0).into_iter().find(|m| matches!(m.mt, MoveType::Rochade(_, rook) if (ind_move.0.0, rook) == ind_move.1)) {
+                    if letter {
+                        return Err(MoveError::UnrightCoordinates)
+                    };
+                    return Ok(mv)
+                };
+                let rights = if self.colorw { 0..2 } else { 2..4 };
+                if rights.into_iter().any(|i| self.rochade[i] != Piece::None && (ind_move.0.0, self.rochade_rooks[i]) == ind_move.1) {
+                    return Err(MoveError::UnallowedRochade)
+                };
+            };
+            if let Some(capt_piece) = self.index_to_piece(ind_move.1) {
+                if piece.piece_to_color() == capt_piece.piece_to_color() {
+                    return Err(MoveError::OwnFigurEnd)
+                }
+            };
+            let promotion = match cmove.get(4..) {
+                None | Some("") => Piece::Queen(self.colorw),
+                Some(l) => match l.chars().next().and_then(|c| Piece::from_char(c.to_ascii_uppercase())) {
+                    Some(Piece::Queen(_)) => Piece::Queen(self.colorw),
+                    Some(Piece::Rook(_)) => Piece::Rook(self.colorw),
+                    Some(Piece::Bishop(_)) => Piece::Bishop(self.colorw),
+                    Some(Piece::Knight(_)) => Piece::Knight(self.colorw),
+                    _ => return Err(MoveError::UnrightCoordinates)
+                }
+            };
+            if let Some(mv) = self.moves_from(ind_move.0).into_iter().find(|m| m.ind_move == ind_move && match m.mt {
+                MoveType::Promotion(np, _) => np == promotion,
+                _ => true
+            }) {
+                if letter && !matches!(mv.mt, MoveType::Promotion(_, _)) {
+                    return Err(MoveError::UnrightCoordinates)
+                };
+                return Ok(mv)
+            };
+            if matches!(piece, Piece::King(_)) && ind_move.0.0 == ind_move.1.0 && ind_move.0.1.abs_diff(ind_move.1.1) == 2 {
+                return Err(MoveError::UnallowedRochade)
+            };
+            if self.pseudo_moves(ind_move.0).iter().any(|m| m.ind_move == ind_move) {
+                Err(MoveError::KingInCheck)
+            } else {
+                Err(MoveError::MoveNotFitPiece(piece))
+            }
+        }
+
+        pub fn move_to_coordinates(&self, mv: &LegalMove) -> String {  // coordinate notation as uci wants it, like LegalMove::to_coordinates() but rochade is always king takes own rook in chess960 games (UCI_Chess960)
+            match mv.mt {
+                MoveType::Rochade(_, rook) if self.chess960 => format!("{}{}", index_to_coordinates(mv.ind_move.0), index_to_coordinates((mv.ind_move.0.0, rook))),
+                _ => mv.to_coordinates()
+            }
+        }
+
+        pub fn legal_moves(&self) -> Vec<LegalMove> {   // all legal moves of the side to move
+            let mut res = Vec::new();
+            for sq in squares(self.bb.color(self.colorw)) {
+                res.append(&mut self.moves_from(field(sq)));
+            };
+            res
+        }
+
+        pub fn moves_from(&self, from: (usize, usize)) -> Vec<LegalMove> { // legal moves of the piece on the given square
+            let piece = self.fields[from.0][from.1];
+            let col = piece.piece_to_color();
+            self.pseudo_moves(from).into_iter().filter(|mv| {
+                let mut bb = self.bb;
+                bb.apply(mv.ind_move, piece, mv.mt);
+                !bb.king_attacked(col)
+            }).collect()
+        }
+
+        fn pseudo_moves(&self, from: (usize, usize)) -> Vec<LegalMove> {    // moves following the movement of the piece, own king may be left in check
+            let piece = self.fields[from.0][from.1];
+            let col = piece.piece_to_color();
+            let mut res = Vec::new();
+            let mut add = |to: (usize, usize)| {
+                match self.fields[to.0][to.1] {
+                    Piece::None => res.push(LegalMove { ind_move: (from, to), mt: MoveType::Normal(piece) }),
+                    cp if cp.piece_to_color() != col => res.push(LegalMove { ind_move: (from, to), mt: MoveType::Capturing(piece, cp) }),
+                    _ => {}
+                }
+            };
+            match piece {
+                Piece::None => {},
+                Piece::Knight(_) | Piece::King(_) | Piece::Rook(_) | Piece::Bishop(_) | Piece::Queen(_) => {
+                    squares(self.bb.attacks(piece, square(from)) & !self.bb.color(col)).map(field).for_each(&mut add);
+                },
+                Piece::Pawn(_) => {
+                    let (dir, start_row) = if col { (-1, 6) } else { (1, 1) };
+                    let promote = |to: (usize, usize), cp: Option<Piece>, res: &mut Vec<LegalMove>| {
+                        for np in [Piece::Queen(col), Piece::Rook(col), Piece::Bishop(col), Piece::Knight(col)] {
+                            res.push(LegalMove { ind_move: (from, to), mt: MoveType::Promotion(np, cp) });
+                        }
+                    };
+                    if let Some(f) = step(from, (dir, 0)) {
+                        if self.field_is_empty(f) && (f.0 == 0 || f.0 == 7) {
+                            promote(f, None, &mut res);
+                        } else if self.field_is_empty(f) {
+                            res.push(LegalMove { ind_move: (from, f), mt: MoveType::Normal(piece) });
+                            if let Some(f2) = step(f, (dir, 0)) {
+                                if from.0 == start_row && self.field_is_empty(f2) {
+                                    res.push(LegalMove { ind_move: (from, f2), mt: MoveType::Normal(piece) });
+                                }
+                            }
+                        }
+                    };
+                    let ep = coordinates_to_index(&self.en_passant).ok();
+                    for f in [step(from, (dir, -1)), step(from, (dir, 1))].into_iter().flatten() {
+                        match self.fields[f.0][f.1] {
+                            Piece::None if ep == Some(f) => res.push(LegalMove { ind_move: (from, f), mt: MoveType::EnPassant((from.0, f.1)) }),
+                            Piece::None => {},
+                            cp if cp.piece_to_color() != col && (f.0 == 0 || f.0 == 7) => promote(f, Some(cp), &mut res),
+                            cp if cp.piece_to_color() != col => res.push(LegalMove { ind_move: (from, f), mt: MoveType::Capturing(piece, cp) }),
+                            _ => {}
+                        }
+                    }
+                }
+            };
+            if let Piece::King(_) = piece {
+                res.append(&mut self.rochade_moves(col));
+            };
+            res
+        }
+
+        fn rochade_moves(&self, col: bool) -> Vec<LegalMove> {  // rochade moves which are allowed by rights, free squares and attacks, the king ends on g or c and the rook on f or d (also in chess960)
+            let row = if col { 7 } else { 0 };
+            let mut res = Vec::new();
+            let king = match self.find_king(col) {
+                Some(k) if k.0 == row && !self.in_check(col) => k,
+                _ => return res
+            };
+            let span = |a: usize, b: usize| min(a, b)..=max(a, b);
+            for i in if col { 0..2 } else { 2..4 } {
+                let (king_end, rook_end) = match self.rochade[i] {
+                    Piece::King(_) => (9, 8),
+                    Piece::Queen(_) => (5, 6),
+                    _ => continue
+                };
+                let rook = self.rochade_rooks[i];
+                if self.fields[row][rook] == Piece::Rook(col)
+                    && span(king.1, king_end).chain(span(rook, rook_end)).all(|s| s == king.1 || s == rook || self.field_is_empty((row, s)))
+                    && span(king.1, king_end).all(|s| !self.is_attacked((row, s), !col)) {
+                    res.push(LegalMove { ind_move: (king, (row, king_end)), mt: MoveType::Rochade(self.rochade[i], rook) });
+                }
+            };
+            res
+        }
+
+        fn apply_board(&mut self, mv: &LegalMove) {   // performs the move on the board only, sidebars and rights stay as they are
+            let ((sr, ss), (er, es)) = mv.ind_move;
+            let piece = self.fields[sr][ss];
+            self.set_field((sr, ss), Piece::None);
+            match mv.mt {
+                MoveType::EnPassant(bind) => self.set_field(bind, Piece::None),
+                MoveType::Rochade(side, rs) => { // both pieces are lifted first, in chess960 the squares may overlap
+                    let rook = self.fields[er][rs];
+                    self.set_field((er, rs), Piece::None);
+                    self.set_field((er, if let Piece::King(_) = side { 8 } else { 6 }), rook);
+                },
+                _ => {}
+            };
+            match mv.mt {
+                MoveType::Promotion(np, _) => self.set_field((er, es), np),
+                _ => self.set_field((er, es), piece)
+            }
+        }
+
+        pub fn find_king(&self, col: bool) -> Option<(usize, usize)> {
+            squares(self.bb.piece(Piece::King(col))).next().map(field)
+        }
+
+        pub fn in_check(&self, col: bool) -> bool { // checks if the king of the given color is attacked
+            self.bb.king_attacked(col)
+        }
+
+        pub fn is_attacked(&self, field: (usize, usize), by: bool) -> bool {   // checks if a piece of the given color attacks the square
+            self.bb.attackers(square(field), by) != 0
+        }
+
+        pub fn to_san(&self, cmove: &str) -> Result<String, MoveError> {   // converts a move in coordinate notation into san, e.g. g1f3 => Nf3
+            Ok(self.move_to_san(&self.legal_move(cmove)?))
+        }
+
+        pub fn move_to_san(&self, mv: &LegalMove) -> String {  // san of a legal move, with disambiguation and check or mate sign
+            let (from, to) = mv.ind_move;
+            let mut res = match mv.mt {
+                MoveType::Rochade(Piece::King(_), _) => String::from("O-O"),
+                MoveType::Rochade(_, _) => String::from("O-O-O"),
+                _ => {
+                    let piece = self.fields[from.0][from.1];
+                    let capture = matches!(mv.mt, MoveType::Capturing(_, _) | MoveType::EnPassant(_) | MoveType::Promotion(_, Some(_)));
+                    let mut res = String::new();
+                    if let Piece::Pawn(_) = piece {
+                        if capture {
+                            res.push_str(&index_to_coordinates(from)[0..1]);
+                        }
+                    } else {
+                        res.push_str(&piece.piece_to_letter().to_uppercase());
+                        let others: Vec<(usize, usize)> = self.legal_moves().into_iter()
+                            .filter(|m| m.ind_move.1 == to && m.ind_move.0 != from && self.fields[m.ind_move.0.0][m.ind_move.0.1] == piece)
+                            .map(|m| m.ind_move.0).collect();
+                        let coord = index_to_coordinates(from);
+                        if !others.is_empty() {
+                            if others.iter().all(|f| f.1 != from.1) {
+                                res.push_str(&coord[0..1]);
+                            } else if others.iter().all(|f| f.0 != from.0) {
+                                res.push_str(&coord[1..2]);
+                            } else {
+                                res.push_str(&coord);
+                            }
+                        }
+                    };
+                    if capture {
+                        res.push('x');
+                    };
+                    res.push_str(&index_to_coordinates(to));
+                    if let MoveType::Promotion(np, _) = mv.mt {
+                        res.push('=');
+                        res.push_str(&np.piece_to_letter().to_uppercase());
+                    };
+                    res
+                }
+            };
+            let mut pos = self.clone();
+            pos.apply_board(mv);
+            pos.colorw = !self.colorw;
+            pos.en_passant = match (mv.mt, from.0.abs_diff(to.0)) {
+                (MoveType::Normal(Piece::Pawn(_)), 2) => index_to_coordinates(((from.0 + to.0) / 2, from.1)),
+                _ => String::from("-")
+            };
+            if pos.in_check(pos.colorw) {
+                res.push(if pos.legal_moves().is_empty() { '#' } else { '+' });
+            };
+            res
+        }
+
+        pub fn from_san(&self, san: &str) -> Result<LegalMove, MoveError> {    // finds the legal move described by san, also accepts 0-0 and promotions without =
+            let san = san.trim().trim_end_matches(['+', '#', '!', '?']);
+            if !san.is_ascii() || san.is_empty() {
+                return Err(MoveError::SanParse)
+            };
+            let moves = self.legal_moves();
+            let found: Vec<LegalMove> = match san {
+                "O-O" | "0-0" => moves.into_iter().filter(|m| matches!(m.mt, MoveType::Rochade(Piece::King(_), _))).collect(),
+                "O-O-O" | "0-0-0" => moves.into_iter().filter(|m| matches!(m.mt, MoveType::Rochade(Piece::Queen(_), _))).collect(),
+                _ => {
+                    let (piece, rest) = match san.chars().next().and_then(Piece::from_char) {
+                        Some(Piece::Pawn(_)) | None => (Piece::Pawn(self.colorw), san),
+                        Some(p) if p.piece_to_color() => (p, &san[1..]),
+                        Some(_) => (Piece::Pawn(self.colorw), san)
+                    };
+                    let piece = match piece {
+                        Piece::King(_) => Piece::King(self.colorw),
+                        Piece::Queen(_) => Piece::Queen(self.colorw),
+                        Piece::Rook(_) => Piece::Rook(self.colorw),
+                        Piece::Bishop(_) => Piece::Bishop(self.colorw),
+                        Piece::Knight(_) => Piece::Knight(self.colorw),
+                        _ => Piece::Pawn(self.colorw)
+                    };
+                    let (rest, promotion) = match rest.char_indices().last() {
+                        Some((i, c)) if c.is_ascii_alphabetic() && i >= 2 => {
+                            let np = match Piece::from_char(c.to_ascii_uppercase()) {
+                                Some(Piece::Queen(_)) => Piece::Queen(self.colorw),
+                                Some(Piece::Rook(_)) => Piece::Rook(self.colorw),
+                                Some(Piece::Bishop(_)) => Piece::Bishop(self.colorw),
+                                Some(Piece::Knight(_)) => Piece::Knight(self.colorw),
+                                _ => return Err(MoveError::SanParse)
+                            };
+                            (rest[..i].trim_end_matches('='), Some(np))
+                        },
+                        _ => (rest, None)
+                    };
+                    let rest = rest.replace(['x', ':'], "");
+                    if rest.len() < 2 {
+                        return Err(MoveError::SanParse)
+                    };
+                    let (hint, dest) = rest.split_at(rest.len() - 2);
+                    let to = coordinates_to_index(dest)?;
+                    let (hint_col, hint_row) = match hint.len() {
+                        0 => (None, None),
+                        1 | 2 => {
+                            let mut chars = hint.chars();
+                            let c = chars.next();
+                            match (c, chars.next()) {
+                                (Some(l @ 'a'..='h'), None) => (Some((l as u8 - b'a') as usize + 3), None),
+                                (Some(d @ '1'..='8'), None) => (None, Some(8 - (d as u8 - b'0') as usize)),
+                                (Some(l @ 'a'..='h'), Some(d @ '1'..='8')) => (Some((l as u8 - b'a') as usize + 3), Some(8 - (d as u8 - b'0') as usize)),
+                                _ => return Err(MoveError::SanParse)
+                            }
+                        },
+                        _ => return Err(MoveError::SanParse)
+                    };
+                    moves.into_iter().filter(|m| {
+                        let (from, mto) = m.ind_move;
+                        mto == to && self.fields[from.0][from.1] == piece
+                            && !matches!(m.mt, MoveType::Rochade(_, _))
+                            && hint_col.is_none_or(|c| c == from.1)
+                            && hint_row.is_none_or(|r| r == from.0)
+                            && match m.mt {
+                                MoveType::Promotion(np, _) => np == promotion.unwrap_or(Piece::Queen(self.colorw)),
+                                _ => promotion.is_none()
+                            }
+                    }).collect()
+                }
+            };
+            match found.as_slice() {
+                [mv] => Ok(*mv),
+                [] => Err(MoveError::SanNoMove),
+                _ => Err(MoveError::SanAmbiguous)
+            }
+        }
+
+        pub fn coordinates_to_piece(&self, coord: &str) -> Result<Option<Piece>, MoveError> { // returns Piece from given square coordinates
+            let res = coordinates_to_index(coord)?;
+            Ok(self.index_to_piece(res))
+        }
+
+        pub fn index_to_piece(&self, ind: (usize, usize)) -> Option<Piece> {    // returns Piece from given square index
             if self.field_is_empty(ind) {
                 None
             } else {
@@ -778,8 +1297,8 @@ pub mod position {
                         } else {
                             return Err(PFError::MoveDoesNotFitType(*mov))
                         }
-                        self.fields[end.0][end.1] = self.fields[start.0][start.1];
-                        self.fields[start.0][start.1] = Piece::None;
+                        self.set_field(end, self.fields[start.0][start.1]);
+                        self.set_field(start, Piece::None);
                         bitlist.update(vec![start], vec![], vec![end]);
                     },
                     PFIType::Rochade(p, coords) => {
@@ -790,8 +1309,8 @@ pub mod position {
                         bitlist.print_out();
                         self.print_out();
                         res.append_wo_pos(pathfinding_custom(FieldUsize::from_tuple(sf), FieldUsize::from_tuple(ef), &mut bitlist, pos)?);
-                        self.fields[ef.0][ef.1] = self.fields[sf.0][sf.1];
-                        self.fields[sf.0][sf.1] = Piece::None;
+                        self.set_field(ef, self.fields[sf.0][sf.1]);
+                        self.set_field(sf, Piece::None);
                         bitlist.update(vec![sf], vec![], vec![ef]);
                     }
                 };
@@ -799,6 +1318,154 @@ pub mod position {
             res.print_out();
             Ok(res)
         }
+
+        // plans the moves which bring the pieces from self to the target position, extra pieces are fetched from and unused pieces sent to the sidebars
+        // returns the moves for pathfinding and the resulting position (target on the board, sidebars as they are after the moves)
+        pub fn setup_moves(&self, target: &Position) -> Result<(Vec<PFIType>, Position), SetupError> {
+            let mut grid = self.fields;
+            let mut pending: Vec<((usize, usize), (usize, usize))> = Vec::new();
+            let mut leaving: Vec<(usize, usize)> = Vec::new();
+            let mut wanted: Vec<(usize, usize)> = Vec::new();
+            for (r, s) in fields_in(3..11) {
+                if grid[r][s] != target.fields[r][s] {
+                    if grid[r][s] != Piece::None {
+                        leaving.push((r, s));
+                    }
+                    if target.fields[r][s] != Piece::None {
+                        wanted.push((r, s));
+                    }
+                }
+            };
+            // pieces leaving a board square are preferred, the sidebars are only used if needed
+            let mut taken_rest: Vec<(usize, usize)> = Vec::new();
+            for dest in wanted {
+                let pce = target.fields[dest.0][dest.1];
+                let from_board = leaving.iter().enumerate()
+                    .filter(|(_, f)| grid[f.0][f.1] == pce)
+                    .min_by_key(|(_, f)| field_dist(**f, dest))
+                    .map(|(i, _)| i);
+                if let Some(i) = from_board {
+                    pending.push((leaving.remove(i), dest));
+                    continue
+                };
+                match self.find_rest_except(pce, dest, &taken_rest) {
+                    Some(f) => {
+                        taken_rest.push(f);
+                        pending.push((f, dest));
+                    },
+                    None => return Err(SetupError::MissingPiece(pce))
+                }
+            };
+            // remaining pieces are not needed anymore and go to an empty square of the sidebars
+            let mut reserved: Vec<(usize, usize)> = Vec::new();
+            for f in leaving {
+                let pce = grid[f.0][f.1];
+                let slot = match self.storage_slot(pce, f, &reserved) {
+                    Some(slot) => slot,
+                    None => return Err(SetupError::NoFreeField)
+                };
+                reserved.push(slot);
+                pending.push((f, slot));
+            };
+            let moves = order_moves(&mut grid, pending)?;
+            let mut res = target.clone();
+            res.fields = grid;
+            res.storage = self.storage;
+            res.resync();
+            Ok((moves, res))
+        }
+    }
+
+    struct ZobristKeys {
+        pieces: [[u64; 64]; 12],
+        rochade: [u64; 4],
+        en_passant: [u64; 8],
+        colorw: u64
+    }
+
+    const ZOBRIST: ZobristKeys = zobrist_keys();
+
+    const fn zobrist_keys() -> ZobristKeys {    // fixed pseudo random keys (splitmix64), same on every run
+        let mut seed: u64 = 0x2545_F491_4F6C_DD1D;
+        let mut keys = [0u64; 12 * 64 + 4 + 8 + 1];
+        let mut i = 0;
+        while i < keys.len() {
+            seed = seed.wrapping_add(0x9E37_79B9_7F4A_7C15);
+            let mut z = seed;
+            z = (z ^ (z >> 30)).wrapping_mul(0xBF58_476D_1CE4_E5B9);
+            z = (z ^ (z >> 27)).wrapping_mul(0x94D0_49BB_1331_11EB);
+            keys[i] = z ^ (z >> 31);
+            i += 1;
+        };
+        let mut pieces = [[0u64; 64]; 12];
+        let mut rochade = [0u64; 4];
+        let mut en_passant = [0u64; 8];
+        i = 0;
+        while i < 12 * 64 {
+            pieces[i / 64][i % 64] = keys[i];
+            i += 1;
+        };
+        i = 0;
+        while i < 4 {
+            rochade[i] = keys[12 * 64 + i];
+            i += 1;
+        };
+        i = 0;
+        while i < 8 {
+            en_passant[i] = keys[12 * 64 + 4 + i];
+            i += 1;
+        };
+        ZobristKeys { pieces, rochade, en_passant, colorw: keys[12 * 64 + 12] }
+    }
+
+    fn zobrist_piece(pce: Piece, field: (usize, usize)) -> u64 {   // key of a piece on a board square, 0 for empty squares
+        match piece_index(pce) {
+            Some(ind) => ZOBRIST.pieces[ind][square(field)],
+            None => 0
+        }
+    }
+
+    fn fields_in<I: Iterator<Item = usize> + Clone>(cols: I) -> impl Iterator<Item = (usize, usize)> {  // all squares of the given columns, row by row
+        (0..8).flat_map(move |r| cols.clone().map(move |s| (r, s)))
+    }
+
+    fn field_dist(a: (usize, usize), b: (usize, usize)) -> usize {   // number of king steps between two squares
+        max(a.0.abs_diff(b.0), a.1.abs_diff(b.1))
+    }
+
+    // orders the moves of several pieces, a move can only be made if its end square is empty, cycles are broken up by parking a piece on a free square
+    // grid is changed accordingly, used for setting up positions and for chess960 rochade
+    fn order_moves(grid: &mut [[Piece; 14]; 8], mut pending: Vec<((usize, usize), (usize, usize))>) -> Result<Vec<PFIType>, SetupError> {
+        let mut moves = Vec::new();
+        while !pending.is_empty() {
+            let i = match pending.iter().position(|(_, e)| grid[e.0][e.1] == Piece::None) {
+                Some(i) => i,
+                None => {
+                    let blocked = pending[0].1;
+                    let j = match pending.iter().position(|(s, _)| *s == blocked) {
+                        Some(j) => j,
+                        None => return Err(SetupError::NoFreeField)
+                    };
+                    let park = fields_in(0..14)
+                        .filter(|f| grid[f.0][f.1] == Piece::None && !pending.iter().any(|(_, e)| e == f))
+                        .min_by_key(|f| field_dist(*f, blocked));
+                    let park = match park {
+                        Some(p) => p,
+                        None => return Err(SetupError::NoFreeField)
+                    };
+                    moves.push(PFIType::Custom(blocked, park));
+                    grid[park.0][park.1] = grid[blocked.0][blocked.1];
+                    grid[blocked.0][blocked.1] = Piece::None;
+                    pending[j].0 = park;
+                    0
+                }
+            };
+            let (s, e) = pending.remove(i);
+            moves.push(PFIType::Custom(s, e));
+            grid[e.0][e.1] = grid[s.0][s.1];
+            grid[s.0][s.1] = Piece::None;
+        };
+        Ok(moves)
     }
 
     #[derive(Debug)]
@@ -1115,29 +1782,24 @@ pub mod position {
         res
     }
 
-    pub fn coordinates_to_index(coordinate: &str) -> Result<(usize, usize), MoveError> {    // converts square description
-        let (letter, number) = coordinate.split_at(1);
-        let mut num: usize = match number.parse() {
-            Ok(n) => n,
-            Err(rr) => return Err(MoveError::MoveParse(rr))
-        };
-        match num {
-            1..9 => {},
-            _ => return Err(MoveError::UnrightCoordinates)
+    pub fn coordinates_to_index(coordinate: &str) -> Result<(usize, usize), MoveError> {    // converts square description, e.g. e4 => (4, 7)
+        match coordinate.as_bytes() {
+            [letter @ b'a'..=b'h', number @ b'1'..=b'8'] => Ok((8 - (number - b'0') as usize, (letter - b'a') as usize + 3)),
+            _ => Err(MoveError::UnrightCoordinates)
         }
-        num = 8 - num;
-        let lett = letter.chars().map(|c| c as usize - 94).collect::<Vec<usize>>()[0];
-        match (num, lett) {
-            (0..8, 3..11) => {},
-            _ => return Err(MoveError::UnrightCoordinates)
-        };
-        Ok((num, lett))
     }
 
-    pub fn ctim(coordinate: &str) -> Result<((usize, usize), (usize, usize)), MoveError> {  // performs coordinates_to_index for whole move
-        let split = coordinate.split_at(2);
-        let first = coordinates_to_index(split.0)?;
-        let second = coordinates_to_index(split.1)?;
+    pub fn index_to_coordinates(ind: (usize, usize)) -> String {   // counterpart to coordinates_to_index
+        format!("{}{}", (b'a' + (ind.1 - 3) as u8) as char, 8 - ind.0)
+    }
+
+    pub fn ctim(coordinate: &str) -> Result<((usize, usize), (usize, usize)), MoveError> {  // performs coordinates_to_index for whole move, a promotion letter at the end is ignored
+        let (first, second) = match (coordinate.get(0..2), coordinate.get(2..4)) {
+            (Some(f), Some(s)) => (f, s),
+            _ => return Err(MoveError::UnrightCoordinates)
+        };
+        let first = coordinates_to_index(first)?;
+        let second = coordinates_to_index(second)?;
         Ok((first, second))
     }
 
@@ -1248,13 +1910,717 @@ pub mod position {
         
 }
 
+pub mod bitboard {
+
+    use crate::position::{MoveType, Piece};
+
+    // squares of the playing area: bit r * 8 + s - 3 stands for fields[r][s], so a8 is bit 0 and h1 is bit 63
+
+    pub const fn square(field: (usize, usize)) -> usize {  // board square of fields to bit index
+        field.0 * 8 + field.1 - 3
+    }
+
+    pub const fn field(sq: usize) -> (usize, usize) {  // counterpart to square()
+        (sq / 8, sq % 8 + 3)
+    }
+
+    pub const fn piece_index(pce: Piece) -> Option<usize> {    // index into Bitboards::pieces, white K Q R N B P, then black
+        let ind = match pce {
+            Piece::King(_) => 0,
+            Piece::Queen(_) => 1,
+            Piece::Rook(_) => 2,
+            Piece::Knight(_) => 3,
+            Piece::Bishop(_) => 4,
+            Piece::Pawn(_) => 5,
+            Piece::None => return None
+        };
+        match pce {
+            Piece::King(false) | Piece::Queen(false) | Piece::Rook(false) | Piece::Knight(false) | Piece::Bishop(false) | Piece::Pawn(false) => Some(ind + 6),
+            _ => Some(ind)
+        }
+    }
+
+    const fn color_index(col: bool) -> usize {
+        if col { 0 } else { 1 }
+    }
+
+    const KNIGHT_STEPS: [(isize, isize); 8] = [(-2, -1), (-2, 1), (-1, -2), (-1, 2), (1, -2), (1, 2), (2, -1), (2, 1)];
+    const KING_STEPS: [(isize, isize); 8] = [(-1, -1), (-1, 0), (-1, 1), (0, -1), (0, 1), (1, -1), (1, 0), (1, 1)];
+    const DIRS: [(isize, isize); 8] = [(-1, 0), (1, 0), (0, -1), (0, 1), (-1, -1), (-1, 1), (1, -1), (1, 1)];  // rook directions first, then bishop directions
+
+    const fn step_sq(sq: usize, (dr, ds): (isize, isize)) -> Option<usize> {
+        let r = (sq / 8) as isize + dr;
+        let s = (sq % 8) as isize + ds;
+        if r < 0 || r > 7 || s < 0 || s > 7 {
+            None
+        } else {
+            Some((r * 8 + s) as usize)
+        }
+    }
+
+    const fn step_table(steps: &[(isize, isize)]) -> [u64; 64] {
+        let mut res = [0u64; 64];
+        let mut sq = 0;
+        while sq < 64 {
+            let mut i = 0;
+            while i < steps.len() {
+                if let Some(t) = step_sq(sq, steps[i]) {
+                    res[sq] |= 1 << t;
+                };
+                i += 1;
+            };
+            sq += 1;
+        };
+        res
+    }
+
+    const fn ray_table() -> [[u64; 64]; 8] {
+        let mut res = [[0u64; 64]; 8];
+        let mut d = 0;
+        while d < 8 {
+            let mut sq = 0;
+            while sq < 64 {
+                let mut cur = step_sq(sq, DIRS[d]);
+                while let Some(t) = cur {
+                    res[d][sq] |= 1 << t;
+                    cur = step_sq(t, DIRS[d]);
+                };
+                sq += 1;
+            };
+            d += 1;
+        };
+        res
+    }
+
+    pub const KNIGHT_ATTACKS: [u64; 64] = step_table(&KNIGHT_STEPS);
+    pub const KING_ATTACKS: [u64; 64] = step_table(&KING_STEPS);
+    pub const PAWN_ATTACKS: [[u64; 64]; 2] = [step_table(&[(-1, -1), (-1, 1)]), step_table(&[(1, -1), (1, 1)])];   // white, black
+    const RAYS: [[u64; 64]; 8] = ray_table();
+
+    fn ray_attacks(sq: usize, d: usize, occupied: u64) -> u64 {    // squares reached in one direction, including the first blocker
+        let ray = RAYS[d][sq];
+        let blockers = ray & occupied;
+        if blockers == 0 {
+            return ray
+        };
+        let first = if DIRS[d].0 > 0 || (DIRS[d].0 == 0 && DIRS[d].1 > 0) {
+            blockers.trailing_zeros()
+        } else {
+            63 - blockers.leading_zeros()
+        };
+        ray ^ RAYS[d][first as usize]
+    }
+
+    pub fn rook_attacks(sq: usize, occupied: u64) -> u64 {
+        (0..4).fold(0, |acc, d| acc | ray_attacks(sq, d, occupied))
+    }
+
+    pub fn bishop_attacks(sq: usize, occupied: u64) -> u64 {
+        (4..8).fold(0, |acc, d| acc | ray_attacks(sq, d, occupied))
+    }
+
+    pub fn squares(mut bb: u64) -> impl Iterator<Item = usize> {   // indices of all set bits, lowest first
+        std::iter::from_fn(move || {
+            if bb == 0 {
+                return None
+            };
+            let sq = bb.trailing_zeros() as usize;
+            bb &= bb - 1;
+            Some(sq)
+        })
+    }
+
+    #[derive(Debug)]
+    #[derive(Clone, Copy, PartialEq, Eq)]
+    pub struct Bitboards {  // playing area of a position as bitboards, the sidebars stay in Position::fields only
+        pub pieces: [u64; 12],
+        pub colors: [u64; 2],   // white, black
+        pub occupied: u64
+    }
+
+    impl Bitboards {
+        pub fn new() -> Self {
+            Bitboards { pieces: [0; 12], colors: [0; 2], occupied: 0 }
+        }
+
+        pub fn from_fields(fields: &[[Piece; 14]; 8]) -> Self {    // generator, reads columns 3..11 of the board
+            let mut res = Self::new();
+            for sq in 0..64 {
+                let (r, s) = field(sq);
+                res.put(sq, fields[r][s]);
+            };
+            res
+        }
+
+        pub fn put(&mut self, sq: usize, pce: Piece) {  // places a piece on an empty square
+            if let Some(i) = piece_index(pce) {
+                self.pieces[i] |= 1 << sq;
+                self.colors[color_index(pce.piece_to_color())] |= 1 << sq;
+                self.occupied |= 1 << sq;
+            }
+        }
+
+        pub fn remove(&mut self, sq: usize, pce: Piece) {   // clears a square which contains the given piece
+            if let Some(i) = piece_index(pce) {
+                self.pieces[i] &= !(1 << sq);
+                self.colors[color_index(pce.piece_to_color())] &= !(1 << sq);
+                self.occupied &= !(1 << sq);
+            }
+        }
+
+        pub fn piece(&self, pce: Piece) -> u64 {
+            match piece_index(pce) {
+                Some(i) => self.pieces[i],
+                None => !self.occupied
+            }
+        }
+
+        pub fn color(&self, col: bool) -> u64 {
+            self.colors[color_index(col)]
+        }
+
+        pub fn attacks(&self, pce: Piece, sq: usize) -> u64 {  // squares attacked by the piece standing on sq
+            match pce {
+                Piece::King(_) => KING_ATTACKS[sq],
+                Piece::Queen(_) => rook_attacks(sq, self.occupied) | bishop_attacks(sq, self.occupied),
+                Piece::Rook(_) => rook_attacks(sq, self.occupied),
+                Piece::Bishop(_) => bishop_attacks(sq, self.occupied),
+                Piece::Knight(_) => KNIGHT_ATTACKS[sq],
+                Piece::Pawn(col) => PAWN_ATTACKS[color_index(col)][sq],
+                Piece::None => 0
+            }
+        }
+
+        pub fn attackers(&self, sq: usize, by: bool) -> u64 {  // pieces of the given color attacking the square
+            let queens = self.piece(Piece::Queen(by));
+            (PAWN_ATTACKS[color_index(!by)][sq] & self.piece(Piece::Pawn(by)))
+                | (KNIGHT_ATTACKS[sq] & self.piece(Piece::Knight(by)))
+                | (KING_ATTACKS[sq] & self.piece(Piece::King(by)))
+                | (rook_attacks(sq, self.occupied) & (self.piece(Piece::Rook(by)) | queens))
+                | (bishop_attacks(sq, self.occupied) & (self.piece(Piece::Bishop(by)) | queens))
+        }
+
+        pub fn king_attacked(&self, col: bool) -> bool {
+            match self.piece(Piece::King(col)) {
+                0 => false,
+                k => self.attackers(k.trailing_zeros() as usize, !col) != 0
+            }
+        }
+
+        pub fn apply(&mut self, ind_move: ((usize, usize), (usize, usize)), piece: Piece, mt: MoveType) { // performs a move on the bitboards only, used to test legality
+            let (from, to) = (square(ind_move.0), square(ind_move.1));
+            self.remove(from, piece);
+            match mt {
+                MoveType::Capturing(_, cp) | MoveType::Promotion(_, Some(cp)) => self.remove(to, cp),
+                MoveType::EnPassant(bind) => self.remove(square(bind), Piece::Pawn(!piece.piece_to_color())),
+                MoveType::Rochade(side, rs) => {   // king is lifted already, so the squares may overlap (chess960)
+                    let re = if let Piece::King(_) = side { 8 } else { 6 };
+                    let rook = Piece::Rook(piece.piece_to_color());
+                    self.remove(square((ind_move.1.0, rs)), rook);
+                    self.put(square((ind_move.1.0, re)), rook);
+                },
+                _ => {}
+            };
+            match mt {
+                MoveType::Promotion(np, _) => self.put(to, np),
+                _ => self.put(to, piece)
+            }
+        }
+    }
+
+    impl Default for Bitboards {
+        fn default() -> Self {
+            Self::new()
+        }
+    }
+}
+
+pub mod pgn {
+
+    use crate::position::{ctim, FenError, MoveError, Position, UpdateError};
+
+    #[derive(Debug)]
+    pub enum PgnError {  // errors when reading or writing a pgn
+        Tag(String),    // tag line which can't be parsed
+        Fen(FenError),  // FEN tag is no valid fen
+        San(usize, String, MoveError),  // half move number and text of a move which isn't legal
+        Update(usize, UpdateError)  // move is legal but can't be performed on the board, e.g. no spare piece for a promotion
+    }
+
+    #[derive(Debug)]
+    pub struct PgnGame {    // game read from a pgn
+        pub tags: Vec<(String, String)>,
+        pub history: Vec<(Position, String)>,   // position before each move and the move in coordinate notation
+        pub position: Position, // position after the last move
+        pub result: String  // 1-0, 0-1, 1/2-1/2 or *
+    }
+
+    impl PgnGame {
+        pub fn tag(&self, name: &str) -> Option<&str> {
+            self.tags.iter().find(|(n, _)| n == name).map(|(_, v)| v.as_str())
+        }
+    }
+
+    pub fn write(tags: &[(String, String)], start: &Position, moves: &[String], result: &str) -> Result<String, PgnError> {  // writes tags and movetext in san, moves are given in coordinate notation
+        let mut res = String::new();
+        for (name, value) in tags {
+            res.push_str(&format!("[{} \"{}\"]\n", name, value.replace('\\', "\\\\").replace('"', "\\\"")));
+        };
+        res.push('\n');
+        let mut pos = start.clone();
+        let mut tokens: Vec<String> = Vec::new();
+        for (i, mv) in moves.iter().enumerate() {
+            if pos.colorw {
+                tokens.push(format!("{}.", pos.moves / 2 + 1));
+            } else if i == 0 {
+                tokens.push(format!("{}...", pos.moves / 2 + 1));
+            };
+            let (san, ind_move) = match (pos.to_san(mv), ctim(mv)) {
+                (Ok(san), Ok(ind_move)) => (san, ind_move),
+                (Err(rr), _) | (_, Err(rr)) => return Err(PgnError::San(i + 1, mv.clone(), rr))
+            };
+            tokens.push(san);
+            match pos.update(ind_move, mv) {
+                Ok(_) => {},
+                Err(rr) => return Err(PgnError::Update(i + 1, rr))
+            };
+        };
+        tokens.push(result.to_string());
+        let mut line = String::new();
+        for t in tokens {   // lines are wrapped before 80 characters
+            if !line.is_empty() && line.len() + t.len() >= 80 {
+                res.push_str(&line);
+                res.push('\n');
+                line.clear();
+            };
+            if !line.is_empty() {
+                line.push(' ');
+            };
+            line.push_str(&t);
+        };
+        res.push_str(&line);
+        res.push('\n');
+        Ok(res)
+    }
+
+    pub fn read(pgn: &str) -> Result<PgnGame, PgnError> {  // reads the first game of a pgn, comments, variations and NAGs are ignored
+        let mut tags = Vec::new();
+        let mut movetext = String::new();
+        for line in pgn.lines() {
+            let line = line.trim();
+            if line.starts_with('%') {  // escape line
+                continue
+            };
+            if line.starts_with('[') && movetext.trim().is_empty() {
+                tags.push(parse_tag(line)?);
+            } else if line.starts_with('[') {   // tags of the next game
+                break
+            } else {
+                movetext.push_str(line);
+                movetext.push('\n');
+            }
+        };
+
+        let mut pos = match tags.iter().find(|(n, _)| n == "FEN") {
+            Some((_, fen)) => match Position::from_fen(fen) {
+                Ok(p) => p,
+                Err(rr) => return Err(PgnError::Fen(rr))
+            },
+            None => Position::new_reset()
+        };
+        let mut history = Vec::new();
+        let mut result = tags.iter().find(|(n, _)| n == "Result").map(|(_, v)| v.clone()).unwrap_or(String::from("*"));
+        for token in strip_movetext(&movetext) {
+            let token = match token.rfind('.') {    // move numbers, also written together with the move (1.e4)
+                Some(i) => &token[i + 1..],
+                None => token.as_str()
+            };
+            match token {
+                "" => continue,
+                "1-0" | "0-1" | "1/2-1/2" | "*" => {
+                    result = token.to_string();
+                    break
+                },
+                _ => {}
+            };
+            let mv = match pos.from_san(token) {
+                Ok(mv) => pos.move_to_coordinates(&mv),
+                Err(rr) => return Err(PgnError::San(history.len() + 1, token.to_string(), rr))
+            };
+            let ind_move = match ctim(&mv) {
+                Ok(i) => i,
+                Err(rr) => return Err(PgnError::San(history.len() + 1, token.to_string(), rr))
+            };
+            let (_, _, oldpos) = match pos.update(ind_move, &mv) {
+                Ok(res) => res,
+                Err(rr) => return Err(PgnError::Update(history.len() + 1, rr))
+            };
+            history.push((oldpos, mv));
+        };
+        Ok(PgnGame { tags, history, position: pos, result })
+    }
+
+    fn parse_tag(line: &str) -> Result<(String, String), PgnError> {   // [Name "Value"]
+        let inner = match line.strip_prefix('[').and_then(|l| l.strip_suffix(']')) {
+            Some(i) => i.trim(),
+            None => return Err(PgnError::Tag(line.to_string()))
+        };
+        let (name, value) = match inner.split_once(char::is_whitespace) {
+            Some(res) => res,
+            None => return Err(PgnError::Tag(line.to_string()))
+        };
+        let value = match value.trim().strip_prefix('"').and_then(|v| v.strip_suffix('"')) {
+            Some(v) => v.replace("\\\"", "\"").replace("\\\\", "\\"),
+            None => return Err(PgnError::Tag(line.to_string()))
+        };
+        Ok((name.to_string(), value))
+    }
+
+    fn strip_movetext(movetext: &str) -> Vec<String> {  // splits the movetext into tokens, removes comments, variations and NAGs
+        let mut res = Vec::new();
+        let mut current = String::new();
+        let mut depth = 0;  // depth of variations
+        let mut chars = movetext.chars();
+        while let Some(c) = chars.next() {
+            match c {
+                '{' => {
+                    for c2 in chars.by_ref() {
+                        if c2 == '}' {
+                            break
+                        }
+                    };
+                    res.push(std::mem::take(&mut current));
+                },
+                ';' => {
+                    for c2 in chars.by_ref() {
+                        if c2 == '\n' {
+                            break
+                        }
+                    };
+                    res.push(std::mem::take(&mut current));
+                },
+                '(' => {
+                    depth += 1;
+                    res.push(std::mem::take(&mut current));
+                },
+                ')' => {
+                    depth -= 1;
+                    current.clear();
+                },
+                _ if depth > 0 => {},
+                c if c.is_whitespace() => res.push(std::mem::take(&mut current)),
+                c => current.push(c)
+            }
+        };
+        res.push(current);
+        res.into_iter().filter(|t| !t.is_empty() && !t.starts_with('$')).collect()
+    }
+}
+
+pub mod search {    // small alpha-beta engine on Position, used when no uci engine is available
+
+    use std::{sync::{atomic::{AtomicBool, Ordering}, Arc}, time::Instant};
+    use crate::bitboard::squares;
+    use crate::position::{LegalMove, MoveType, Piece, Position};
+
+    pub const MATE: i32 = 32000;    // score of the side which mates on the board, mates further away score less
+    const MAX_PLY: usize = 64;
+
+    const VALUES: [i32; 6] = [0, 900, 500, 320, 330, 100];  // K Q R N B P, in the order of bitboard::piece_index()
+
+    // piece-square tables from the view of white with a8 first, like the bit indices of the bitboards, the last one is the king in the endgame
+    const PST: [[i32; 64]; 7] = [
+        [
+            -30, -40, -40, -50, -50, -40, -40, -30,
+            -30, -40, -40, -50, -50, -40, -40, -30,
+            -30, -40, -40, -50, -50, -40, -40, -30,
+            -30, -40, -40, -50, -50, -40, -40, -30,
+            -20, -30, -30, -40, -40, -30, -30, -20,
+            -10, -20, -20, -20, -20, -20, -20, -10,
+             20,  20,   0,   0,   0,   0,  20,  20,
+             20,  30,  10,   0,   0,  10,  30,  20
+        ],
+        [
+            -20, -10, -10,  -5,  -5, -10, -10, -20,
+            -10,   0,   0,   0,   0,   0,   0, -10,
+            -10,   0,   5,   5,   5,   5,   0, -10,
+             -5,   0,   5,   5,   5,   5,   0,  -5,
+              0,   0,   5,   5,   5,   5,   0,  -5,
+            -10,   5,   5,   5,   5,   5,   0, -10,
+            -10,   0,   5,   0,   0,   0,   0, -10,
+            -20, -10, -10,  -5,  -5, -10, -10, -20
+        ],
+        [
+              0,   0,   0,   0,   0,   0,   0,   0,
+              5,  10,  10,  10,  10,  10,  10,   5,
+             -5,   0,   0,   0,   0,   0,   0,  -5,
+             -5,   0,   0,   0,   0,   0,   0,  -5,
+             -5,   0,   0,   0,   0,   0,   0,  -5,
+             -5,   0,   0,   0,   0,   0,   0,  -5,
+             -5,   0,   0,   0,   0,   0,   0,  -5,
+              0,   0,   0,   5,   5,   0,   0,   0
+        ],
+        [
+            -50, -40, -30, -30, -30, -30, -40, -50,
+            -40, -20,   0,   0,   0,   0, -20, -40,
+            -30,   0,  10,  15,  15,  10,   0, -30,
+            -30,   5,  15,  20,  20,  15,   5, -30,
+            -30,   0,  15,  20,  20,  15,   0, -30,
+            -30,   5,  10,  15,  15,  10,   5, -30,
+            -40, -20,   0,   5,   5,   0, -20, -40,
+            -50, -40, -30, -30, -30, -30, -40, -50
+        ],
+        [
+            -20, -10, -10, -10, -10, -10, -10, -20,
+            -10,   0,   0,   0,   0,   0,   0, -10,
+            -10,   0,   5,  10,  10,   5,   0, -10,
+            -10,   5,   5,  10,  10,   5,   5, -10,
+            -10,   0,  10,  10,  10,  10,   0, -10,
+            -10,  10,  10,  10,  10,  10,  10, -10,
+            -10,   5,   0,   0,   0,   0,   5, -10,
+            -20, -10, -10, -10, -10, -10, -10, -20
+        ],
+        [
+              0,   0,   0,   0,   0,   0,   0,   0,
+             50,  50,  50,  50,  50,  50,  50,  50,
+             10,  10,  20,  30,  30,  20,  10,  10,
+              5,   5,  10,  25,  25,  10,   5,   5,
+              0,   0,   0,  20,  20,   0,   0,   0,
+              5,  -5, -10,   0,   0, -10,  -5,   5,
+              5,  10,  10, -20, -20,  10,  10,   5,
+              0,   0,   0,   0,   0,   0,   0,   0
+        ],
+        [
+            -50, -40, -30, -20, -20, -30, -40, -50,
+            -30, -20, -10,   0,   0, -10, -20, -30,
+            -30, -10,  20,  30,  30,  20, -10, -30,
+            -30, -10,  30,  40,  40,  30, -10, -30,
+            -30, -10,  30,  40,  40,  30, -10, -30,
+            -30, -10,  20,  30,  30,  20, -10, -30,
+            -30, -30,   0,   0,   0,   0, -30, -30,
+            -50, -30, -30, -30, -30, -30, -30, -50
+        ]
+    ];
+
+    fn pieces(col: bool) -> [Piece; 6] {    // in the order of VALUES and PST
+        [Piece::King(col), Piece::Queen(col), Piece::Rook(col), Piece::Knight(col), Piece::Bishop(col), Piece::Pawn(col)]
+    }
+
+    fn value(pce: Piece) -> i32 {
+        match pieces(pce.piece_to_color()).iter().position(|p| *p == pce) {
+            Some(i) => VALUES[i],
+            None => 0
+        }
+    }
+
+    pub fn evaluate(pos: &Position) -> i32 {    // material and piece-square tables in centipawns, from the view of the side to move
+        let bb = &pos.bb;
+        let officers: i32 = [true, false].iter().flat_map(|c| pieces(*c).into_iter().enumerate().skip(1).take(4))
+            .map(|(i, p)| bb.piece(p).count_ones() as i32 * VALUES[i]).sum();
+        let endgame = officers <= 2 * (VALUES[2] + VALUES[3]);  // about a rook and a minor piece each
+        let mut score = 0;
+        for col in [true, false] {
+            let sign = if col {1} else {-1};
+            for (i, pce) in pieces(col).into_iter().enumerate() {
+                let table = if i == 0 && endgame {6} else {i};
+                for sq in squares(bb.piece(pce)) {
+                    let sq = if col {sq} else {sq ^ 56};    // black looks at the board from the other side
+                    score += sign * (VALUES[i] + PST[table][sq]);
+                };
+            };
+        };
+        if pos.colorw {score} else {-score}
+    }
+
+    pub fn mate_in(score: i32) -> Option<i32> {    // moves until mate for mate scores, negative if the side to move gets mated
+        if score.abs() < MATE - MAX_PLY as i32 {
+            None
+        } else if score > 0 {
+            Some((MATE - score + 1) / 2)
+        } else {
+            Some(-(MATE + score + 1) / 2)
+        }
+    }
+
+    #[derive(Debug)]
+    #[derive(Clone, Copy, PartialEq, Eq)]
+    pub struct Limits { // when the search ends, the first iteration is always finished
+        pub depth: u32,
+        pub nodes: Option<u64>,
+        pub deadline: Option<Instant>
+    }
+
+    impl Limits {
+        pub fn depth(depth: u32) -> Self {
+            Limits { depth, nodes: None, deadline: None }
+        }
+    }
+
+    #[derive(Debug)]
+    #[derive(Clone, PartialEq, Eq)]
+    pub struct Iteration {  // result of one finished depth of the iterative deepening
+        pub depth: u32,
+        pub score: i32, // centipawns from the view of the side to move, see mate_in()
+        pub nodes: u64,
+        pub pv: Vec<LegalMove>  // best move first, never empty
+    }
+
+    pub struct Searcher {
+        limits: Limits,
+        stop: Arc<AtomicBool>,  // set from outside to end the search early
+        history: Vec<u64>,  // hashes of the game before the root and of the current line, for repetitions
+        nodes: u64,
+        abortable: bool,    // false until the first iteration is done
+        aborted: bool
+    }
+
+    impl Searcher {
+        pub fn new(limits: Limits) -> Self {
+            Searcher { limits, stop: Arc::new(AtomicBool::new(false)), history: Vec::new(), nodes: 0, abortable: false, aborted: false }
+        }
+
+        pub fn with_stop(mut self, stop: Arc<AtomicBool>) -> Self {
+            self.stop = stop;
+            self
+        }
+
+        pub fn with_history(mut self, keys: Vec<u64>) -> Self {    // hashes of the positions before the root, a position which comes again counts as draw
+            self.history = keys;
+            self
+        }
+
+        pub fn search(&mut self, pos: &Position, mut report: impl FnMut(&Iteration)) -> Option<Iteration> {    // iterative deepening, gives back the last finished iteration, None if there is no legal move
+            let mut best: Option<Iteration> = None;
+            for depth in 1..=self.limits.depth.max(1) {
+                let prev = best.as_ref().map(|b| b.pv.clone()).unwrap_or_default();
+                let mut line = Vec::new();
+                let score = self.alphabeta(pos, depth, 0, -MATE, MATE, &prev, &mut line);
+                if self.aborted || line.is_empty() {
+                    break
+                };
+                let it = Iteration { depth, score, nodes: self.nodes, pv: line };
+                report(&it);
+                best = Some(it);
+                self.abortable = true;
+                if mate_in(score).is_some() {  // a deeper search won't find a shorter mate
+                    break
+                };
+            };
+            best
+        }
+
+        fn out_of_limits(&mut self) -> bool {  // checked every 1024 nodes
+            if self.abortable && self.nodes.is_multiple_of(1024) {
+                self.aborted = self.stop.load(Ordering::Relaxed)
+                    || self.limits.nodes.is_some_and(|n| self.nodes >= n)
+                    || self.limits.deadline.is_some_and(|d| Instant::now() >= d);
+            };
+            self.aborted
+        }
+
+        #[allow(clippy::too_many_arguments)]
+        fn alphabeta(&mut self, pos: &Position, depth: u32, ply: usize, mut alpha: i32, beta: i32, prev: &[LegalMove], line: &mut Vec<LegalMove>) -> i32 {
+            self.nodes += 1;
+            if self.out_of_limits() {
+                return 0
+            };
+            if ply > 0 && (pos.since_pawn_major >= 100 || pos.insufficient_material() || self.history.contains(&pos.hash)) {
+                return 0
+            };
+            if depth == 0 || ply >= MAX_PLY {
+                return self.quiescence(pos, ply, alpha, beta)
+            };
+            let moves = pos.legal_moves();
+            if moves.is_empty() {
+                return if pos.in_check(pos.colorw) {-(MATE - ply as i32)} else {0}
+            };
+            let mut best = -MATE;
+            self.history.push(pos.hash);
+            for mv in ordered(pos, moves, prev.first()) {
+                let next = if prev.first() == Some(&mv) {&prev[1..]} else {&[]};
+                let mut child = Vec::new();
+                let score = -self.alphabeta(&pos.make_move(&mv), depth - 1, ply + 1, -beta, -alpha, next, &mut child);
+                if self.aborted {
+                    break
+                };
+                if score > best {
+                    best = score;
+                    if score > alpha {
+                        alpha = score;
+                        line.clear();
+                        line.push(mv);
+                        line.append(&mut child);
+                    };
+                };
+                if alpha >= beta {
+                    break
+                };
+            };
+            self.history.pop();
+            best
+        }
+
+        fn quiescence(&mut self, pos: &Position, ply: usize, mut alpha: i32, beta: i32) -> i32 { // only captures and promotions, all moves when in check
+            self.nodes += 1;
+            if self.out_of_limits() {
+                return 0
+            };
+            if ply >= MAX_PLY {
+                return evaluate(pos)
+            };
+            let check = pos.in_check(pos.colorw);
+            let mut best = if check {-(MATE - ply as i32)} else {evaluate(pos)};
+            if best >= beta {
+                return best
+            };
+            alpha = alpha.max(best);
+            let moves = pos.legal_moves();
+            if moves.is_empty() && !check {
+                return 0    // stalemate
+            };
+            let moves = moves.into_iter().filter(|mv| check || matches!(mv.mt, MoveType::Capturing(_, _) | MoveType::EnPassant(_) | MoveType::Promotion(_, _))).collect();
+            for mv in ordered(pos, moves, None) {
+                let score = -self.quiescence(&pos.make_move(&mv), ply + 1, -beta, -alpha);
+                if self.aborted {
+                    break
+                };
+                best = best.max(score);
+                alpha = alpha.max(score);
+                if alpha >= beta {
+                    break
+                };
+            };
+            best
+        }
+    }
+
+    fn ordered(pos: &Position, mut moves: Vec<LegalMove>, first: Option<&LegalMove>) -> Vec<LegalMove> {  // the move of the last iteration first, then captures of valuable pieces with cheap ones and promotions
+        let rank = |mv: &LegalMove| {
+            let attacker = value(pos.fields[mv.ind_move.0.0][mv.ind_move.0.1]);
+            match mv.mt {
+                _ if Some(mv) == first => i32::MAX,
+                MoveType::Capturing(_, victim) => 10 * value(victim) - attacker,
+                MoveType::EnPassant(_) => 10 * VALUES[5] - attacker,
+                MoveType::Promotion(p, captured) => value(p) + captured.map(|c| 10 * value(c)).unwrap_or(0),
+                _ => -10000
+            }
+        };
+        moves.sort_by_key(|mv| -(rank(mv) as i64));
+        moves
+    }
+
+    pub fn best_move(pos: &Position, depth: u32) -> Option<LegalMove> {    // best move after a search of the given depth, None if there is no legal move
+        Searcher::new(Limits::depth(depth)).search(pos, |_| {}).map(|it| it.pv[0])
+    }
+}
+
 
 
 #[cfg(test)]
 mod tests {
     use mctrl::motor::FieldUsize;
 
-    use crate::position::{MoveError, MoveType, Position, Piece, BitList};
+    use crate::position::{FenError, MoveError, MoveType, Position, Piece, BitList, PFIType, State, DrawR, StorageLayout, UpdateError, ctim, coordinates_to_index};
 
     use super::*;
 
@@ -1276,7 +2642,7 @@ mod tests {
 
     #[test]
     fn it_works2() {
-        let result: &Result<MoveType, MoveError> = &get_position().validate_move_possibility("f1b5");
+        let result: &Result<MoveType, MoveError> = &get_position().validate_move_possibility("c1f4");
         assert_eq!(format!("{:?}", result) , format!("{:?}", Ok::<position::MoveType, MoveError>(MoveType::Normal(Piece::Bishop(true)))));
     }
 
@@ -1342,4 +2708,505 @@ mod tests {
         let result = &Piece::Knight(true).check_field("a1", "b4");
         assert_eq!(*result, false);
     }
-}
\ No newline at end of file
+
+    fn play_setup(from: &Position, to: &Position) -> Position {    // executes the setup moves on the fields, checks that no piece lands on an occupied square
+        let (moves, res) = from.setup_moves(to).unwrap();
+        let mut fields = from.fields;
+        for mov in moves {
+            match mov {
+                PFIType::Custom(s, e) => {
+                    assert_ne!(fields[s.0][s.1], Piece::None);
+                    assert_eq!(fields[e.0][e.1], Piece::None);
+                    fields[e.0][e.1] = fields[s.0][s.1];
+                    fields[s.0][s.1] = Piece::None;
+                },
+                _ => panic!()
+            }
+        };
+        assert_eq!(fields, res.fields);
+        res
+    }
+
+    #[test]
+    fn it_works12() {
+        let target = Position::from_fen("r1bqkb1r/pppppppp/2n2n2/8/8/2N2N2/PPPPPPPP/R1BQKB1R w KQkq - 4 3").unwrap();
+        let res = play_setup(&Position::new_reset(), &target);
+        assert_eq!(res.to_fen(), target.to_fen());
+    }
+
+    #[test]
+    fn it_works13() {
+        let target = Position::from_fen("4k3/8/8/8/8/8/8/4K3 w - - 0 1").unwrap();
+        let res = play_setup(&Position::new_reset(), &target);
+        assert_eq!(res.to_fen(), target.to_fen());
+        let back = play_setup(&res, &Position::new_reset());
+        assert_eq!(back.to_fen(), Position::new_reset().to_fen());
+    }
+
+    #[test]
+    fn it_works14() {   // kings swap places, needs a parking square
+        let target = Position::from_fen("4K3/8/8/8/8/8/8/4k3 w - - 0 1").unwrap();
+        let res = play_setup(&Position::new_reset(), &target);
+        assert_eq!(res.to_fen(), target.to_fen());
+    }
+
+    #[test]
+    fn it_works15() {
+        assert_eq!(Position::new_reset().legal_moves().len(), 20);
+        let result = &get_position().validate_move_possibility("f1b5");    // pawn on e2 is in the way
+        assert_eq!(format!("{:?}", result), format!("{:?}", Err::<MoveType, MoveError>(MoveError::MoveNotFitPiece(Piece::Bishop(true)))));
+        let result = get_position().validate_move_possibility("e5d6").unwrap();
+        assert_eq!(result, MoveType::EnPassant((3, 6)));
+    }
+
+    #[test]
+    fn it_works16() {   // pinned knight, castling through check
+        let pos = Position::from_fen("r3k2r/8/8/b7/8/8/3N4/R3K2R w KQkq - 0 1").unwrap();
+        let result = &pos.validate_move_possibility("d2f3");
+        assert_eq!(format!("{:?}", result), format!("{:?}", Err::<MoveType, MoveError>(MoveError::KingInCheck)));
+        assert_eq!(pos.validate_move_possibility("e1g1").unwrap(), MoveType::Rochade(Piece::King(true), 10));
+        assert_eq!(pos.validate_move_possibility("e1c1").unwrap(), MoveType::Rochade(Piece::Queen(true), 3));
+        let pos = Position::from_fen("r3k2r/8/8/8/8/8/6q1/R3K2R w KQkq - 0 1").unwrap();
+        let result = &pos.validate_move_possibility("e1g1");
+        assert_eq!(format!("{:?}", result), format!("{:?}", Err::<MoveType, MoveError>(MoveError::UnallowedRochade)));
+    }
+
+    #[test]
+    fn it_works17() {
+        let pos = Position::new_reset();
+        let mut moves: Vec<String> = pos.moves_from(position::coordinates_to_index("g1").unwrap()).iter().map(|m| m.to_coordinates()).collect();
+        moves.sort();
+        assert_eq!(moves, vec!["g1f3", "g1h3"]);
+        let pos = Position::from_fen("4k3/8/8/8/8/8/4r3/4K3 w - - 0 1").unwrap();
+        assert!(pos.in_check(true));
+        assert_eq!(pos.legal_moves().len(), 3);
+    }
+
+    #[test]
+    fn it_works18() {
+        let result = Position::from_fen("8/1q6/5k2/K7/Pp6/8/8/8 w - - 1 54").unwrap().game_state();
+        assert_eq!(format!("{:?}", result), format!("{:?}", State::Draw(DrawR::Stalemate)));
+        let result = Position::from_fen("8/3R4/3kp3/1Q6/1P6/8/1PK1P3/8 b - - 8 44").unwrap().game_state();
+        assert_eq!(format!("{:?}", result), format!("{:?}", State::Mate(true)));
+        let result = Position::from_fen("1R5k/5ppp/5n2/8/8/5K2/8/8 b - - 0 1").unwrap().game_state();
+        assert_eq!(format!("{:?}", result), format!("{:?}", State::Normal));
+    }
+
+    #[test]
+    fn it_works19() {   // works without stockfish
+        let mut pos = Position::from_fen("rnbqkbnr/pppp1ppp/8/4p3/6P1/5P2/PPPPP2P/RNBQKBNR b KQkq g3 0 2").unwrap();
+        let (state, _, _) = pos.update(position::ctim("d8h4").unwrap(), "d8h4").unwrap();
+        assert_eq!(format!("{:?}", state), format!("{:?}", State::Mate(false)));
+        assert_eq!(pos.to_fen(), "rnb1kbnr/pppp1ppp/8/4p3/6Pq/5P2/PPPPP2P/RNBQKBNR w KQkq - 1 3");
+    }
+
+    #[test]
+    fn it_works20() {   // underpromotion, spare knight comes from the sidebar
+        let mut pos = Position::from_fen("8/4P2k/8/8/8/8/8/4K3 w - - 0 1").unwrap();
+        assert_eq!(pos.legal_moves().len(), 9);
+        assert_eq!(pos.validate_move_possibility("e7e8n").unwrap(), MoveType::Promotion(Piece::Knight(true), None));
+        assert_eq!(pos.validate_move_possibility("e7e8").unwrap(), MoveType::Promotion(Piece::Queen(true), None));
+        assert_eq!(format!("{:?}", pos.legal_move("e1e2q")), format!("{:?}", Err::<(), _>(MoveError::UnrightCoordinates)));   // a letter only for promotions
+        let (_, moves, _) = pos.update(position::ctim("e7e8n").unwrap(), "e7e8n").unwrap();
+        assert_eq!(pos.to_fen(), "4N3/7k/8/8/8/8/8/4K3 b - - 0 1");
+        assert_eq!(format!("{:?}", moves[0]), format!("{:?}", PFIType::Custom((5, 1), (0, 7))));
+        assert_eq!(pos.fields[5][1], Piece::None);
+        assert_eq!(pos.fields.iter().flatten().filter(|p| **p == Piece::Pawn(true)).count(), 8);
+    }
+
+    #[test]
+    fn it_works21() {
+        let mut pos = Position::from_fen("1r5k/P7/8/8/8/8/8/4K3 w - - 0 1").unwrap();
+        let mut moves: Vec<String> = pos.moves_from((1, 3)).iter().map(|m| m.to_coordinates()).collect();
+        moves.sort();
+        assert_eq!(moves, vec!["a7a8b", "a7a8n", "a7a8q", "a7a8r", "a7b8b", "a7b8n", "a7b8q", "a7b8r"]);
+        pos.fields[4][0] = Piece::None;
+        pos.fields[4][1] = Piece::None;
+        let result = pos.update(position::ctim("a7b8b").unwrap(), "a7b8b");
+        assert_eq!(format!("{:?}", result.err()), "Some(CleaningError(MissingPiece(Bishop(true))))");
+    }
+
+    fn play(pos: &mut Position, moves: &[&str]) {
+        for mv in moves {
+            pos.update(position::ctim(mv).unwrap(), mv).unwrap();
+            assert_eq!(pos.hash, pos.compute_hash());
+            assert_eq!(pos.bb, bitboard::Bitboards::from_fields(&pos.fields));
+        }
+    }
+
+    #[test]
+    fn it_works22() {   // incremental hash stays equal to the full calculation
+        let mut pos = Position::new_reset();
+        play(&mut pos, &["e2e4", "d7d5", "e4d5", "c7c5", "d5c6", "g8f6", "c6b7", "e7e6", "b7a8q", "f8e7", "g1f3", "e8g8", "f1e2", "e7b4", "e1g1"]);
+        let mut pos = Position::from_fen("r3k2r/8/8/8/8/8/8/R3K2R w KQkq - 0 1").unwrap();
+        play(&mut pos, &["a1a8", "e8d7"]);
+        assert_eq!(pos.to_fen(), "R6r/3k4/8/8/8/8/8/4K2R w K - 1 2");
+    }
+
+    #[test]
+    fn it_works23() {   // transpositions have the same key, rochade rights and possible en passant change it
+        let mut a = Position::new_reset();
+        play(&mut a, &["g1f3", "g8f6", "b1c3", "b8c6"]);
+        let mut b = Position::new_reset();
+        play(&mut b, &["b1c3", "b8c6", "g1f3", "g8f6"]);
+        assert_eq!(a.repetition_key(), b.repetition_key());
+        let mut c = Position::new_reset();
+        play(&mut c, &["e2e4"]);
+        let d = Position::from_fen("rnbqkbnr/pppppppp/8/8/4P3/8/PPPP1PPP/RNBQKBNR b KQkq - 0 1").unwrap();
+        assert_ne!(c.hash, d.hash);
+        assert_eq!(c.repetition_key(), d.repetition_key());
+        let e = Position::from_fen("rnbqkbnr/pppppppp/8/8/4P3/8/PPPP1PPP/RNBQKBNR b Qkq - 0 1").unwrap();
+        assert_ne!(d.repetition_key(), e.repetition_key());
+        let f = Position::from_fen("rnbqkbnr/ppp1pppp/8/8/3pP3/8/PPPP1PPP/RNBQKBNR b KQkq e3 0 1").unwrap();
+        let g = Position::from_fen("rnbqkbnr/ppp1pppp/8/8/3pP3/8/PPPP1PPP/RNBQKBNR b KQkq - 0 1").unwrap();
+        assert_ne!(f.repetition_key(), g.repetition_key());
+    }
+
+    #[test]
+    fn it_works24() {
+        for fen in ["8/8/4k3/8/8/3K4/8/8 w - - 0 1", "8/8/4k3/8/8/3K4/8/6n1 w - - 0 1", "8/8/4k3/8/8/3KB3/8/8 b - - 0 1", "3b4/8/4k3/8/8/3KB3/8/8 w - - 0 1", "8/1b6/4k3/8/8/3K4/6B1/3B4 w - - 0 1"] {
+            let result = Position::from_fen(fen).unwrap().game_state();
+            assert_eq!(format!("{:?}", result), format!("{:?}", State::Draw(DrawR::InsufficientMaterial)));
+        };
+        for fen in ["8/8/4k3/8/8/3KN3/8/6n1 w - - 0 1", "2b5/8/4k3/8/8/3KB3/8/8 w - - 0 1", "8/8/4k3/8/8/3K4/7P/8 w - - 0 1", "8/8/4k3/8/8/3KNN2/8/8 w - - 0 1"] {
+            let result = Position::from_fen(fen).unwrap().game_state();
+            assert_eq!(format!("{:?}", result), format!("{:?}", State::Normal));
+        };
+    }
+
+    #[test]
+    fn it_works25() {
+        let pos = Position::new_reset();
+        assert_eq!(pos.to_san("g1f3").unwrap(), "Nf3");
+        assert_eq!(pos.to_san("e2e4").unwrap(), "e4");
+        assert_eq!(pos.from_san("Nc3").unwrap().to_coordinates(), "b1c3");
+        assert_eq!(format!("{:?}", pos.from_san("Nd2")), format!("{:?}", Err::<position::LegalMove, MoveError>(MoveError::SanNoMove)));
+        let pos = Position::from_fen("r3k2r/1P6/8/3p4/4P3/8/8/R3K1NR w KQkq - 0 1").unwrap();
+        assert_eq!(pos.to_san("e4d5").unwrap(), "exd5");
+        assert_eq!(pos.to_san("e1c1").unwrap(), "O-O-O");
+        assert_eq!(pos.to_san("b7a8q").unwrap(), "bxa8=Q+");
+        assert_eq!(pos.to_san("b7b8n").unwrap(), "b8=N");
+        assert_eq!(pos.from_san("bxa8=Q+").unwrap().to_coordinates(), "b7a8q");
+        assert_eq!(pos.from_san("b8N").unwrap().to_coordinates(), "b7b8n");
+        assert_eq!(pos.from_san("0-0-0").unwrap().to_coordinates(), "e1c1");
+        assert_eq!(format!("{:?}", pos.from_san("O-O")), format!("{:?}", Err::<position::LegalMove, MoveError>(MoveError::SanNoMove)));
+        let pos = Position::from_fen("4k3/8/8/8/8/2N3N1/8/R3K2R w - - 0 1").unwrap();
+        assert_eq!(pos.to_san("c3e4").unwrap(), "Nce4");
+        assert_eq!(pos.to_san("a1d1").unwrap(), "Rd1");
+        assert_eq!(format!("{:?}", pos.from_san("Ne4")), format!("{:?}", Err::<position::LegalMove, MoveError>(MoveError::SanAmbiguous)));
+        assert_eq!(pos.from_san("Rhf1").unwrap().to_coordinates(), "h1f1");
+        let pos = Position::from_fen("6k1/5ppp/8/8/8/8/8/R3K3 w - - 0 1").unwrap();
+        assert_eq!(pos.to_san("a1a8").unwrap(), "Ra8#");
+        let pos = Position::from_fen("4k3/8/8/8/8/1N6/8/1N2K3 w - - 0 1").unwrap();
+        assert_eq!(pos.to_san("b3d2").unwrap(), "N3d2");
+        assert_eq!(pos.from_san("N1d2").unwrap().to_coordinates(), "b1d2");
+    }
+
+    #[test]
+    fn it_works26() {
+        let pgn = "[Event \"Test\"]\n[White \"Player\"]\n[Black \"Stockfish\"]\n[Result \"1-0\"]\n\n1. e4 e5 {open game} 2. Nf3 (2. f4 exf4) Nc6 3.Bc4 $1 Nf6?? ; two knights\n4. Ng5 d5 5. exd5 Nxd5 6. Nxf7 Kxf7 7. Qf3+ Ke6 8. Nc3 1-0\n";
+        let game = pgn::read(pgn).unwrap();
+        assert_eq!(game.tag("Black"), Some("Stockfish"));
+        assert_eq!(game.result, "1-0");
+        assert_eq!(game.history.len(), 15);
+        assert_eq!(game.history[2].1, "g1f3");
+        assert_eq!(game.position.to_fen(), "r1bq1b1r/ppp3pp/2n1k3/3np3/2B5/2N2Q2/PPPP1PPP/R1B1K2R b KQ - 3 8");
+        let moves: Vec<String> = game.history.iter().map(|(_, m)| m.clone()).collect();
+        let written = pgn::write(&game.tags, &Position::new_reset(), &moves, &game.result).unwrap();
+        assert!(written.starts_with("[Event \"Test\"]\n"));
+        assert!(written.contains("\n\n1. e4 e5 2. Nf3 Nc6 3. Bc4 Nf6 4. Ng5 d5 5. exd5 Nxd5 6. Nxf7 Kxf7 7. Qf3+ Ke6\n8. Nc3 1-0\n"));
+        let again = pgn::read(&written).unwrap();
+        assert_eq!(again.position.to_fen(), game.position.to_fen());
+
+        let start = Position::from_fen("4k3/8/8/8/8/8/8/R3K3 b Q - 0 30").unwrap();
+        let written = pgn::write(&[(String::from("FEN"), start.to_fen())], &start, &["e8d7".to_string(), "e1c1".to_string()], "*").unwrap();
+        assert!(written.ends_with("\n30... Kd7 31. O-O-O+ *\n"));
+        assert_eq!(pgn::read(&written).unwrap().position.to_fen(), "8/3k4/8/8/8/8/8/2KR4 b - - 2 31");
+        assert!(matches!(pgn::read("1. e4 e4"), Err(pgn::PgnError::San(2, _, _))));
+    }
+
+    #[test]
+    fn it_works27() {
+        let cases: [(&str, &str); 16] = [
+            ("rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq", "FieldCount(3)"),
+            ("rnbqkbnr/pppppppp/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 0 1", "RankCount(7)"),
+            ("rnbqkbnr/pppppppp/9/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 0 1", "UnknownPiece('9')"),
+            ("rnbqkbnr/pppppppp/7/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 0 1", "RankLength(6)"),
+            ("rnbqkbnr/ppppppppp/8/8/8/8/PPPPPPP/RNBQKBNR w KQkq - 0 1", "RankLength(7)"),
+            ("rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNX w KQkq - 0 1", "UnknownPiece('X')"),
+            ("rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR x KQkq - 0 1", "SideToMove(\"x\")"),
+            ("rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQ1BNR w kq - 0 1", "KingCount(true)"),
+            ("rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBKKBNR w kq - 0 1", "TooManyPieces(King(true))"),
+            ("rnbqkbnr/pppppppp/8/8/8/2N5/PPPPPPPP/RNBQKBNR w KQkq - 0 1", "TooManyPieces(Knight(true))"),
+            ("rnbqkbnP/ppppppp1/8/8/8/8/1PPPPPPP/RNBQKBNR w KQkq - 0 1", "PawnOnBackRank"),
+            ("rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBN1 w KQkq - 0 1", "RochadeMismatch('K')"),
+            ("rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KKkq - 0 1", "Rochade(\"KKkq\")"),
+            ("rnbqkbnr/pppppppp/8/8/4P3/8/PPPP1PPP/RNBQKBNR b KQkq e4 0 1", "EnPassant(\"e4\")"),
+            ("rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 0 0", "FullMove(None)"),
+            ("rnb1kbnr/pppp1ppp/8/4p3/5PPq/8/PPPPP2P/RNBQKBNR b KQkq - 1 3", "OpponentInCheck"),
+        ];
+        for (fen, err) in cases {
+            assert_eq!(format!("{:?}", Position::from_fen(fen).map(|p| p.to_fen())), format!("Err({})", err), "{}", fen);
+        };
+        assert_eq!(Position::from_fen("rnbqkbnr/pppppppp/8/8/4P3/8/PPPP1PPP/RNBQKBNR b KQkq e3").unwrap().to_fen(), "rnbqkbnr/pppppppp/8/8/4P3/8/PPPP1PPP/RNBQKBNR b KQkq e3 0 1");
+        assert!(matches!(Position::from_fen("rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - x 1"), Err(FenError::HalfMove(_))));
+        assert_eq!(format!("{:?}", position::coordinates_to_index("é1")), format!("{:?}", Err::<(usize, usize), MoveError>(MoveError::UnrightCoordinates)));
+        assert_eq!(format!("{:?}", position::coordinates_to_index("h1")), "Ok((7, 10))");
+    }
+
+    struct XorShift(u64);   // small deterministic rng for the fuzz tests
+
+    impl XorShift {
+        fn next(&mut self) -> u64 {
+            self.0 ^= self.0 << 13;
+            self.0 ^= self.0 >> 7;
+            self.0 ^= self.0 << 17;
+            self.0
+        }
+
+        fn below(&mut self, n: usize) -> usize {
+            (self.next() % n as u64) as usize
+        }
+    }
+
+    fn check_fen(fen: &str) {   // from_fen must not panic, accepted fens have to survive a round trip
+        if let Ok(pos) = Position::from_fen(fen) {
+            let again = Position::from_fen(&pos.to_fen()).unwrap();
+            assert_eq!(again.to_fen(), pos.to_fen(), "{}", fen);
+            assert_eq!(pos.hash, pos.compute_hash());
+        }
+    }
+
+    #[test]
+    fn it_works28() {   // fuzzing with random strings and mutated valid fens
+        let mut rng = XorShift(0x9e3779b97f4a7c15);
+        let alphabet: Vec<char> = "pnbrqkPNBRQK0123456789/ -wbKQkqaefhx é".chars().collect();
+        for _ in 0..3000 {
+            let len = rng.below(70);
+            let fen: String = (0..len).map(|_| alphabet[rng.below(alphabet.len())]).collect();
+            check_fen(&fen);
+            let _ = position::coordinates_to_index(&fen.chars().take(2).collect::<String>());
+            let _ = position::ctim(&fen);
+            let pos = Position::new_reset();
+            let _ = pos.validate_move_possibility(&fen);
+            let _ = pos.from_san(&fen);
+            let _ = pgn::read(&fen);
+        };
+        let valid = ["rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 0 1", "rnbqkbnr/1p2pppp/8/p1ppP3/P7/8/1PP1PPPP/RNBQKBNR w KQkq d6 0 6",
+            "r3k2r/1P6/8/3p4/4P3/8/8/R3K1NR w KQkq - 0 1", "8/8/4k3/8/8/3K4/8/8 w - - 0 1", "4k3/8/8/8/8/8/8/R3K3 b Q - 0 30"];
+        for _ in 0..5000 {
+            let mut fen: Vec<char> = valid[rng.below(valid.len())].chars().collect();
+            for _ in 0..1 + rng.below(3) {
+                let i = rng.below(fen.len());
+                match rng.below(3) {
+                    0 => fen[i] = alphabet[rng.below(alphabet.len())],
+                    1 => fen.insert(i, alphabet[rng.below(alphabet.len())]),
+                    _ => {
+                        fen.remove(i);
+                    }
+                };
+            };
+            check_fen(&fen.into_iter().collect::<String>());
+        };
+    }
+
+    #[test]
+    fn it_works29() {   // bitboard attacks and synchronisation with the fields during random games
+        use bitboard::{bishop_attacks, rook_attacks, square, Bitboards, KNIGHT_ATTACKS};
+        let a1 = square((7, 3));
+        assert_eq!(KNIGHT_ATTACKS[a1], (1 << square((5, 4))) | (1 << square((6, 5))));
+        let pos = Position::from_fen("4k3/8/8/3p4/8/1P6/8/R3K3 w - - 0 1").unwrap();
+        assert_eq!(rook_attacks(a1, pos.bb.occupied).count_ones(), 11);
+        assert_eq!(bishop_attacks(square((4, 7)), pos.bb.occupied).count_ones(), 10);
+        assert!(pos.is_attacked((4, 5), true) && pos.is_attacked((4, 7), false));
+        assert!(!pos.is_attacked((4, 6), true));
+        let mut rng = XorShift(0x2545f4914f6cdd1d);
+        for _ in 0..20 {
+            let mut pos = Position::new_reset();
+            for _ in 0..80 {
+                let moves: Vec<position::LegalMove> = pos.legal_moves().into_iter().filter(|m| !matches!(m.mt, MoveType::Promotion(_, _))).collect();
+                if moves.is_empty() {
+                    break
+                };
+                let mv = moves[rng.below(moves.len())].to_coordinates();
+                if pos.update(position::ctim(&mv).unwrap(), &mv).is_err() {
+                    break
+                };
+                assert_eq!(pos.bb, Bitboards::from_fields(&pos.fields), "{}", pos.to_fen());
+                assert_eq!(pos.hash, pos.compute_hash());
+            }
+        };
+    }
+
+    const PERFT_POSITIONS: [(&str, &[u64]); 10] = [   // standard perft positions with the known node counts for depth 1, 2, ...
+        ("rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 0 1", &[20, 400, 8902, 197281, 4865609]),
+        ("r3k2r/p1ppqpb1/bn2pnp1/3PN3/1p2P3/2N2Q1p/PPPBBPPP/R3K2R w KQkq - 0 1", &[48, 2039, 97862, 4085603]),  // kiwipete
+        ("8/2p5/3p4/KP5r/1R3p1k/8/4P1P1/8 w - - 0 1", &[14, 191, 2812, 43238, 674624]),   // en passant with pins along the rank
+        ("r3k2r/Pppp1ppp/1b3nbN/nP6/BBP1P3/q4N2/Pp1P2PP/R2Q1RK1 w kq - 0 1", &[6, 264, 9467, 422333]),
+        ("rnbq1k1r/pp1Pbppp/2p5/8/2B5/8/PPP1NnPP/RNBQK2R w KQ - 1 8", &[44, 1486, 62379, 2103487]),
+        ("r4rk1/1pp1qppp/p1np1n2/2b1p1B1/2B1P1b1/P1NP1N2/1PP1QPPP/R4RK1 w - - 0 10", &[46, 2079, 89890, 3894594]),
+        ("r3k2r/8/8/8/8/8/8/R3K2R w KQkq - 0 1", &[26, 568, 13744, 314346]),   // all rochades
+        ("8/8/8/KPp4r/8/8/8/7k w - c6 0 1", &[4]), // en passant would expose the king
+        ("bqnb1rkr/pp3ppp/3ppn2/2p5/5P2/P2P4/NPP1P1PP/BQ1BNRKR w HFhf - 2 9", &[21, 528, 12189, 326672, 8146062]),   // chess960
+        ("2nnrbkr/p1qppppp/8/1ppb4/6PP/3PP3/PPP2P2/BQNNRBKR w HEhe - 1 9", &[21, 807, 18002, 667366])
+    ];
+
+    #[test]
+    fn it_works30() {   // perft up to a total of about 100000 nodes per position
+        for (fen, counts) in PERFT_POSITIONS {
+            let pos = Position::from_fen(fen).unwrap();
+            for (depth, count) in counts.iter().enumerate().take_while(|(_, c)| **c < 100000) {
+                assert_eq!(pos.perft(depth as u32 + 1), *count, "{} depth {}", fen, depth + 1);
+            }
+        };
+        let divide = Position::new_reset().divide(2);
+        assert_eq!(divide.len(), 20);
+        assert!(divide.iter().all(|(_, n)| *n == 20));
+    }
+
+    #[test]
+    #[ignore]   // slow in debug builds, run with cargo test --release -- --ignored
+    fn it_works31() {
+        for (fen, counts) in PERFT_POSITIONS {
+            let pos = Position::from_fen(fen).unwrap();
+            for (depth, count) in counts.iter().enumerate() {
+                assert_eq!(pos.perft(depth as u32 + 1), *count, "{} depth {}", fen, depth + 1);
+            }
+        };
+    }
+
+    fn play_rochade(fen: &str, mv: &str) -> Position { // plays a chess960 rochade, checks the planned moves and gives back the position after it
+        let mut pos = Position::from_fen(fen).unwrap();
+        let mut fields = pos.fields;
+        let (_, moves, _) = pos.update(ctim(mv).unwrap(), mv).unwrap();
+        for mov in moves {
+            match mov {
+                PFIType::Custom(s, e) => {
+                    assert_ne!(fields[s.0][s.1], Piece::None);
+                    assert_eq!(fields[e.0][e.1], Piece::None);
+                    fields[e.0][e.1] = fields[s.0][s.1];
+                    fields[s.0][s.1] = Piece::None;
+                },
+                _ => panic!()
+            }
+        };
+        assert_eq!(fields, pos.fields);
+        pos
+    }
+
+    #[test]
+    fn it_works32() {   // chess960
+        assert_eq!(Position::new_chess960(518).unwrap().fields, Position::new_reset().fields);
+        assert_eq!(Position::new_chess960(0).unwrap().to_fen(), "bbqnnrkr/pppppppp/8/8/8/8/PPPPPPPP/BBQNNRKR w HFhf - 0 1");
+        assert!(Position::new_chess960(960).is_none());
+        for n in [0, 1, 100, 518, 959] {
+            let pos = Position::new_chess960(n).unwrap();
+            let fen = pos.to_fen();
+            assert_eq!(Position::from_fen(&fen).unwrap().to_fen(), fen);
+            assert_eq!(pos.legal_moves().len(), 20, "{}", fen);
+        };
+
+        // x-fen rights are taken as the outermost rook, mixing in the column of a rook makes it chess960
+        let pos = Position::from_fen("bbqnnrkr/pppppppp/8/8/8/8/PPPPPPPP/BBQNNRKR w KQkq - 0 1").unwrap();
+        assert_eq!(pos.rochade_rooks, [10, 8, 10, 8]);
+        assert!(pos.chess960);
+        assert_eq!(pos.to_fen(), "bbqnnrkr/pppppppp/8/8/8/8/PPPPPPPP/BBQNNRKR w HFhf - 0 1");
+        assert!(!Position::from_fen("r3k2r/8/8/8/8/8/8/R3K2R w KQkq - 0 1").unwrap().chess960);
+        assert_eq!(format!("{:?}", Position::from_fen("4k3/8/8/8/8/8/8/4K1R1 w F - 0 1").err()), "Some(RochadeMismatch('F'))");
+
+        // king and rook swap their squares, one of them has to make room
+        let pos = play_rochade("4k3/8/8/8/8/8/8/5KR1 w G - 0 1", "f1g1");
+        assert_eq!(pos.to_fen(), "4k3/8/8/8/8/8/8/5RK1 b - - 1 1");
+        // king stays, the rook passes it
+        let pos = play_rochade("4k3/8/8/8/8/8/8/1RK5 w B - 0 1", "c1b1");
+        assert_eq!(pos.to_fen(), "4k3/8/8/8/8/8/8/2KR4 b - - 1 1");
+        // rook goes to the square of the king
+        let pos = play_rochade("1r2k3/8/8/8/8/8/8/1R1K4 w B - 0 1", "d1b1");
+        assert_eq!(pos.to_fen(), "1r2k3/8/8/8/8/8/8/2KR4 b - - 1 1");
+
+        // rochade in coordinates is king takes own rook, the normal king move stays possible
+        let pos = Position::from_fen("4k3/8/8/8/8/8/8/RK6 w A - 0 1").unwrap();
+        assert_eq!(pos.validate_move_possibility("b1c1").unwrap(), MoveType::Normal(Piece::King(true)));
+        let mv = pos.legal_move("b1a1").unwrap();
+        assert_eq!(mv.mt, MoveType::Rochade(Piece::Queen(true), 3));
+        assert_eq!(mv.ind_move.1, coordinates_to_index("c1").unwrap());
+        assert_eq!(mv.to_coordinates(), "b1a1");
+        assert_eq!(pos.to_san("b1a1").unwrap(), "O-O-O");
+        assert_eq!(pos.from_san("O-O-O").unwrap(), mv);
+        let pos = Position::from_fen("4k3/8/8/8/8/8/8/RK3r2 w A - 0 1").unwrap();   // c1 is attacked
+        assert_eq!(format!("{:?}", pos.validate_move_possibility("b1a1")), "Err(UnallowedRochade)");
+    }
+
+    #[test]
+    fn it_works33() {   // storage in the sidebars
+        let mut pos = Position::new_reset();
+        assert_eq!(Position::from_fen(&pos.to_fen()).unwrap().fields, pos.fields);
+        assert_eq!(pos.inventory().get(&Piece::Queen(false)), Some(&1));
+        assert_eq!(pos.add_rest(Piece::Queen(false), (0, 10)).unwrap(), (0, 12));
+        assert_eq!(pos.fields[0][12], Piece::Queen(false));
+        assert_eq!(pos.add_rest(Piece::King(false), (0, 10)).unwrap(), (0, 11));
+        assert_eq!(pos.fields[0][11], Piece::King(false));
+        // nearest home square first, then the free column, a third rook doesn't overwrite the others
+        assert_eq!(pos.add_rest(Piece::Bishop(true), (4, 5)).unwrap(), (4, 1));
+        assert_eq!(pos.add_rest(Piece::Bishop(true), (4, 5)).unwrap(), (4, 0));
+        let rooks: Vec<(usize, usize)> = (0..3).map(|_| pos.add_rest(Piece::Rook(true), (6, 3)).unwrap()).collect();
+        assert_eq!(rooks, vec![(6, 1), (6, 0), (6, 2)]);
+        assert_eq!(pos.inventory().get(&Piece::Rook(true)), Some(&3));
+        // a full pawn area spills over into the free column and then into other home squares
+        for _ in 0..8 {
+            pos.add_rest(Piece::Pawn(false), (4, 10)).unwrap();
+        };
+        assert_eq!(pos.add_rest(Piece::Pawn(false), (4, 10)).unwrap(), (4, 11));
+        let mut pawns = 9;
+        while pos.add_rest(Piece::Pawn(false), (4, 10)).is_ok() {
+            pawns += 1;
+        };
+        assert_eq!(pawns, 21);  // 24 squares, queens and king were there before
+        assert_eq!(format!("{:?}", pos.add_rest(Piece::Pawn(false), (4, 10))), "Err(StorageFull(Pawn(false)))");
+        assert_eq!(format!("{:?}", pos.add_rest(Piece::None, (4, 10))), "Err(NoPiece)");
+        // retrieval
+        assert_eq!(pos.take_rest(Piece::Rook(true), (5, 3)).unwrap(), (6, 2));
+        assert_eq!(pos.inventory().get(&Piece::Rook(true)), Some(&2));
+        assert_eq!(format!("{:?}", pos.take_rest(Piece::Knight(true), (5, 3))), "Err(MissingPiece(Knight(true)))");
+        // other layout, everything is free
+        let mut pos = Position::new_reset();
+        pos.storage = StorageLayout { slots: [[Piece::None; 6]; 8] };
+        assert_eq!(pos.add_rest(Piece::Knight(false), (3, 10)).unwrap(), (3, 11));
+    }
+
+    #[test]
+    fn it_works34() {   // built-in engine
+        use crate::search::{best_move, evaluate, mate_in, Limits, Searcher, MATE};
+        assert_eq!(evaluate(&Position::new_reset()), 0);
+        let pos = Position::from_fen("4k3/8/8/3q4/8/8/8/3RK3 w - - 0 1").unwrap();
+        assert!(evaluate(&pos) < -300);
+        assert_eq!(best_move(&pos, 2).map(|mv| mv.to_coordinates()), Some(String::from("d1d5")));
+        // back rank mate, found in the first iteration already
+        let pos = Position::from_fen("6k1/5ppp/8/8/8/8/5PPP/R5K1 w - - 0 1").unwrap();
+        let mut depths = Vec::new();
+        let it = Searcher::new(Limits::depth(4)).search(&pos, |it| depths.push(it.depth)).unwrap();
+        assert_eq!((it.pv[0].to_coordinates(), mate_in(it.score)), (String::from("a1a8"), Some(1)));
+        assert_eq!(depths, vec![1]);
+        assert_eq!(mate_in(-(MATE - 2)), Some(-1));
+        assert_eq!(mate_in(250), None);
+        // stalemate, no move
+        let pos = Position::from_fen("7k/5Q2/6K1/8/8/8/8/8 b - - 0 1").unwrap();
+        assert_eq!(best_move(&pos, 3), None);
+    }
+
+    #[test]
+    fn it_works35() {   // a move which fails on the sidebars leaves the position as it was
+        let mut pos = Position::new_reset();
+        for mv in ["e2e4", "d7d5"] {
+            pos.update(ctim(mv).unwrap(), mv).unwrap();
+        };
+        for r in 0..8 {
+            for c in (0..3).chain(11..14) {
+                pos.fields[r][c] = Piece::Pawn(true);
+            };
+        };
+        let before = pos.clone();
+        assert!(matches!(pos.update(ctim("e4d5").unwrap(), "e4d5"), Err(UpdateError::CleaningError(_))));
+        assert_eq!((pos.to_fen(), pos.fields, pos.hash), (before.to_fen(), before.fields, before.hash));
+    }
+}
@@ -1,137 +1,899 @@
 use std::{
-    io::{Error, Read, Write},
-    process::{Child, Command, Stdio},
-    string::FromUtf8Error,
-    time::Duration,
+    env, fs,
+    io::{BufRead, BufReader, Error, ErrorKind, Write},
+    path::{Path, PathBuf},
+    process::{Child, ChildStdin, Command, Stdio},
+    sync::mpsc::{self, Receiver, RecvTimeoutError},
+    thread,
+    time::{Duration, Instant},
 };
 
-// specifies which sf binary to use depending on architecture
+// default engine depending on architecture, any other uci engine can be chosen at runtime with Engine::new(), see find_engine()
 
 #[cfg(target_arch = "aarch64")]
-const PATH_TO_STOCKFISH: &str = "../stockfish/sfs/sf_raspi";
+pub const DEFAULT_PATH: &str = "../stockfish/sfs/sf_raspi";
+
+#[cfg(not(target_arch = "aarch64"))]
+pub const DEFAULT_PATH: &str = "../stockfish/sfs/sf_ubuntu";
+
 #[cfg(target_arch = "aarch64")]
-const  WELCOME_MESSAGE: &str = "Stockfish dev-20260426-1a882efc by the Stockfish developers (see AUTHORS file)\nreadyok\n";
+const BUNDLED_NAME: &str = "sf_raspi";   // same binary, shipped in sfs/ next to the executable, see bundled_path()
 
+#[cfg(not(target_arch = "aarch64"))]
+const BUNDLED_NAME: &str = "sf_ubuntu";
 
-#[cfg(target_arch = "x86_64")] 
-const PATH_TO_STOCKFISH: &str = "../stockfish/sfs/sf_ubuntu";
-#[cfg(target_arch = "x86_64")] 
-const  WELCOME_MESSAGE: &str = "Stockfish dev-20260426-1a882efc by the Stockfish developers (see AUTHORS file)\nreadyok\n";
+pub const ENGINE_ENV: &str = "CHESS_ENGINE";    // environment variable with the path of the engine, overrides the config file
+const CONFIG_FILE: &str = "chess_firmware/engine.conf";    // inside $XDG_CONFIG_HOME or ~/.config
+const PATH_NAMES: [&str; 2] = ["stockfish", "stockfish.exe"];   // searched in PATH if nothing else is found
 
+const HANDSHAKE_TIMEOUT: Duration = Duration::from_secs(10);   // for uciok and readyok, loading big networks takes a while
+const SEARCH_MARGIN: Duration = Duration::from_secs(2);    // how much longer than the given time a search may take before it gets stopped
+const STOP_TIMEOUT: Duration = Duration::from_secs(1); // waiting for bestmove after stop, the engine gets killed afterwards
 
-pub fn get_move(fen: &str, elo: u32, time: u32) -> Result<SFResults, SFErrors> {    // gives back error or move, given the position and sf parameters
-    let mut sf = match new_sf() { // creates sf child
-        Ok(sf) => sf,
-        Err(rr) => return Err(rr),
-    };
 
-    // creates io handles of the sf child
-    let mut sfin = match sf.stdin.take() {
-        Some(sfin) => sfin,
-        None => return Err(SFErrors::SFInCreation),
-    };
-    let mut sfout = match sf.stdout.take() {
-        Some(sfout) => sfout,
-        None => return Err(SFErrors::SFOutCreation),
-    };
+pub fn get_move(fen: &str, elo: u32, time: u32) -> Result<SFResults, SFErrors> {    // gives back error or move, starts the default engine just for this call, see Engine for games
+    Engine::discover()?.get_move(fen, elo, time)
+}
+
+pub fn get_eval(fen: &str, time: u32) -> Result<SFScore, SFErrors> {   // evaluates the position at full strength, starts the default engine just for this call
+    Engine::discover()?.get_eval(fen, time)
+}
 
-    // creating buffer for op, sending test-sequence, matching test-op to exspected value
-    let mut output_buffer: [u8; WELCOME_MESSAGE.len()] = [0; WELCOME_MESSAGE.len()];
-    match sfin.write_all("isready\n".as_bytes()) {
-        Ok(_) => {}
-        Err(rr) => return Err(SFErrors::SFInWriting(rr)),
+pub fn find_engine(config: &EngineConfig) -> Option<String> {  // path of the engine: environment variable, config file, bundled binary, DEFAULT_PATH, PATH
+    if let Ok(path) = env::var(ENGINE_ENV) {
+        if !path.is_empty() {
+            return Some(path)
+        };
     };
-    match sfout.read_exact(&mut output_buffer) {
-        Ok(_) => {}
-        Err(rr) => return Err(SFErrors::SFOutReading(rr)),
+    if let Some(path) = &config.path {
+        return Some(path.clone())
     };
-    match String::from_utf8(output_buffer.to_vec()) {
-        Ok(s) => match s.as_str() {
-            WELCOME_MESSAGE => {}
-            _ => return Err(SFErrors::SFTesting),
-        },
-        Err(rr) => return Err(SFErrors::SFOutReadingParsing(rr)),
+    if let Some(path) = bundled_path() {
+        return Some(path.to_string_lossy().to_string())
     };
-
-    // giving sf command to determine next move, waiting for answer and ending sf
-    match sfin.write_all(&format!("position fen {}\nsetoption name UCI_Elo value {}\nsetoption name UCI_LimitStrength value true \ngo movetime {}\n", fen, elo, time).as_bytes()) {
-        Ok(_) => {}
-        Err(rr) => return Err(SFErrors::SFInWriting(rr)),
-    };
-    std::thread::sleep(Duration::from_millis((time + 100) as u64));
-    match sfin.write_all(&format!("quit\n").as_bytes()) {
-        Ok(_) => {}
-        Err(rr) => return Err(SFErrors::SFInWriting(rr)),
+    if Path::new(DEFAULT_PATH).is_file() {
+        return Some(DEFAULT_PATH.to_string())
     };
+    let dirs = env::var_os("PATH")?;
+    env::split_paths(&dirs)
+        .flat_map(|d| PATH_NAMES.iter().map(move |n| d.join(n)))
+        .find(|p| p.is_file())
+        .map(|p| p.to_string_lossy().to_string())
+}
 
-    // reading result, parse to string, extracting move
-    let mut res_buffer: Vec<u8> = Vec::new();
-    match sfout.read_to_end(&mut res_buffer) {
-        Ok(_) => {}
-        Err(rr) => return Err(SFErrors::SFOutReading(rr)),
-    };
-    let res = match String::from_utf8(res_buffer) {
-        Ok(s) => {
-            //println!("{}", s);
-            s
+fn bundled_path() -> Option<PathBuf> {    // engine in sfs/ next to the running executable, independent of the working directory
+    let exe = env::current_exe().ok()?;
+    let path = exe.parent()?.join("sfs").join(BUNDLED_NAME);
+    if path.is_file() {Some(path)} else {None}
+}
+
+pub fn config_path() -> Option<PathBuf> {   // where the engine config is stored, None without a home directory
+    match (env::var_os("XDG_CONFIG_HOME"), env::var_os("HOME")) {
+        (Some(dir), _) if !dir.is_empty() => Some(PathBuf::from(dir).join(CONFIG_FILE)),
+        (_, Some(home)) => Some(PathBuf::from(home).join(".config").join(CONFIG_FILE)),
+        _ => None
+    }
+}
+
+#[derive(Debug)]
+#[derive(Clone, Default, PartialEq, Eq)]
+pub struct EngineConfig {   // engine settings of the config file, lines like "Hash = 256", options which are None aren't sent
+    pub path: Option<String>,
+    pub threads: Option<i64>,
+    pub hash: Option<i64>,  // in MB
+    pub syzygy_path: Option<String>,    // folders with tablebases, separated by : (; on windows)
+    pub skill_level: Option<i64>    // 0..20, only used while the strength isn't limited by elo
+}
+
+impl EngineConfig {
+    pub fn load() -> Result<Self, ConfigError> {    // reads the config file, no file means no settings
+        let path = match config_path() {
+            Some(p) => p,
+            None => return Ok(EngineConfig::default())
+        };
+        match fs::read_to_string(&path) {
+            Ok(text) => EngineConfig::parse(&text),
+            Err(rr) if rr.kind() == ErrorKind::NotFound => Ok(EngineConfig::default()),
+            Err(rr) => Err(ConfigError::Io(rr))
         }
-        Err(rr) => return Err(SFErrors::SFOutReadingParsing(rr)),
-    };
-    let res_list: Vec<&str> = res.trim_ascii_end().split("\n").collect();
-    let n = match res_list.last() {
-        Some(s) => {
-            //println!("{}", s);
-            *s
+    }
+
+    pub fn save(&self) -> Result<PathBuf, ConfigError> {    // writes the config file, gives back its path
+        let path = match config_path() {
+            Some(p) => p,
+            None => return Err(ConfigError::NoConfigDir)
+        };
+        if let Some(dir) = path.parent() {
+            match fs::create_dir_all(dir) {
+                Ok(_) => {},
+                Err(rr) => return Err(ConfigError::Io(rr))
+            };
+        };
+        match fs::write(&path, self.to_text()) {
+            Ok(_) => Ok(path),
+            Err(rr) => Err(ConfigError::Io(rr))
         }
-        None => return Err(SFErrors::SFProcessing),
-    };
+    }
+
+    pub fn parse(text: &str) -> Result<Self, ConfigError> { // empty lines and lines starting with # are skipped, keys are case insensitive
+        let mut res = EngineConfig::default();
+        for (i, line) in text.lines().enumerate() {
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') {
+                continue
+            };
+            let (key, value) = match line.split_once('=') {
+                Some((k, v)) => (k.trim().to_ascii_lowercase(), v.trim()),
+                None => return Err(ConfigError::Syntax(i + 1))
+            };
+            let number = || value.parse::<i64>().map_err(|_| ConfigError::Value(key.clone()));
+            match key.as_str() {
+                "path" => res.path = Some(value.to_string()),
+                "threads" => res.threads = Some(number()?),
+                "hash" => res.hash = Some(number()?),
+                "syzygypath" => res.syzygy_path = Some(value.to_string()),
+                "skill level" => res.skill_level = Some(number()?),
+                _ => return Err(ConfigError::UnknownKey(key))
+            };
+        };
+        Ok(res)
+    }
 
-    // determines type of result and returning it
-    match n {
-        "bestmove (none)" => {
-            match res_list[res_list.len() - 2].split(" ").collect::<Vec<&str>>().iter().nth_back(1) {
-                Some(v) => {
-                    match *v {
-                        "mate" => Ok(SFResults::Mate),
-                        "cp" => Ok(SFResults::Stalemate),
-                        _ => Err(SFErrors::SFProcessing)
+    pub fn to_text(&self) -> String {   // counterpart to parse()
+        let mut res = String::from("# engine settings of chess_firmware\n");
+        let lines = [
+            ("path", self.path.clone()),
+            ("Threads", self.threads.map(|v| v.to_string())),
+            ("Hash", self.hash.map(|v| v.to_string())),
+            ("SyzygyPath", self.syzygy_path.clone()),
+            ("Skill Level", self.skill_level.map(|v| v.to_string()))
+        ];
+        for (key, value) in lines {
+            if let Some(v) = value {
+                res.push_str(&format!("{} = {}\n", key, v));
+            };
+        };
+        res
+    }
+
+    pub fn options(&self) -> Vec<(&'static str, OptionValue)> {  // uci options which are set
+        let mut res = Vec::new();
+        if let Some(v) = self.threads {
+            res.push(("Threads", OptionValue::Spin(v)));
+        };
+        if let Some(v) = self.hash {
+            res.push(("Hash", OptionValue::Spin(v)));
+        };
+        if let Some(v) = &self.syzygy_path {
+            res.push(("SyzygyPath", OptionValue::String(v.clone())));
+        };
+        if let Some(v) = self.skill_level {
+            res.push(("Skill Level", OptionValue::Spin(v)));
+        };
+        res
+    }
+}
+
+#[derive(Debug)]
+pub enum ConfigError {  // errors of reading and writing the config file
+    Io(Error),
+    NoConfigDir,    // neither XDG_CONFIG_HOME nor HOME is set
+    Syntax(usize),  // line without =
+    UnknownKey(String),
+    Value(String)   // value of the key isn't a number
+}
+
+pub trait Connection: Send {    // line based channel to a uci engine, a Process or a mock::MockEngine for tests
+    fn send(&mut self, commands: &str) -> Result<(), SFErrors>;
+    fn read_line(&mut self, timeout: Option<Duration>) -> Result<String, SFErrors>;    // next output line without newline, Timeout or SFDied if none comes, None waits as long as it takes
+    fn try_read_line(&mut self) -> Option<String>;  // output line which is already there, doesn't wait
+    fn is_alive(&mut self) -> bool;
+    fn kill(&mut self);
+    fn close(&mut self);    // waits for the end of the engine after quit
+    fn reconnect(&mut self) -> Result<Box<dyn Connection>, SFErrors>;  // new connection to the same engine, used for restarts
+    fn id(&self) -> Option<u32> {   // process id if there is one
+        None
+    }
+}
+
+pub struct Process {    // engine running as child process
+    path: String,
+    child: Child,
+    sfin: ChildStdin,
+    sfout: Receiver<String>     // output lines, read by a separate thread so that waiting can time out
+}
+
+impl Process {
+    pub fn new(path: &str) -> Result<Self, SFErrors> {  // starts the engine at the given path
+        let mut sf = new_sf(path)?;
+
+        // creates io handles of the sf child
+        let sfin = match sf.stdin.take() {
+            Some(sfin) => sfin,
+            None => return Err(SFErrors::SFInCreation),
+        };
+        let sfout = match sf.stdout.take() {
+            Some(sfout) => sfout,
+            None => return Err(SFErrors::SFOutCreation),
+        };
+        let (tx, rx) = mpsc::channel();
+        thread::spawn(move || {    // ends when the engine closes its output or the process handle is gone
+            let mut reader = BufReader::new(sfout);
+            let mut buf = Vec::new();
+            loop {
+                buf.clear();
+                match reader.read_until(b'\n', &mut buf) {
+                    Ok(0) | Err(_) => break,
+                    Ok(_) => {
+                        if tx.send(String::from_utf8_lossy(&buf).trim_end().to_string()).is_err() {
+                            break
+                        }
                     }
-                },
-                None => Err(SFErrors::SFProcessing)
-            }
-        },
-        _ => {
-            if n.chars().count() == 25 {
-                let wlist: Vec<&str> = n.split_whitespace().collect();
-                if wlist.len() == 4 {
-                    Ok(SFResults::Normal(String::from(wlist[1])))
-                } else {
-                    Err(SFErrors::SFProcessing)
                 }
-            } else if n.chars().count() == 13 {
-                let wlist: Vec<&str> = n.split_whitespace().collect();
-                if wlist.len() == 2 {
-                    Ok(SFResults::Normal(String::from(wlist[1])))
-                } else {
-                    Err(SFErrors::SFProcessing)
-                }
-            } else {
-                Err(SFErrors::SFProcessing)
             }
+        });
+        Ok(Process { path: path.to_string(), child: sf, sfin, sfout: rx })
+    }
+}
+
+impl Connection for Process {
+    fn send(&mut self, commands: &str) -> Result<(), SFErrors> {
+        match self.sfin.write_all(commands.as_bytes()).and_then(|_| self.sfin.flush()) {
+            Ok(_) => Ok(()),
+            Err(rr) => Err(SFErrors::SFInWriting(rr))
+        }
+    }
+
+    fn read_line(&mut self, timeout: Option<Duration>) -> Result<String, SFErrors> {
+        match timeout {
+            Some(t) => match self.sfout.recv_timeout(t) {
+                Ok(line) => Ok(line),
+                Err(RecvTimeoutError::Timeout) => Err(SFErrors::Timeout),
+                Err(RecvTimeoutError::Disconnected) => Err(SFErrors::SFDied)
+            },
+            None => match self.sfout.recv() {
+                Ok(line) => Ok(line),
+                Err(_) => Err(SFErrors::SFDied)
+            }
+        }
+    }
+
+    fn try_read_line(&mut self) -> Option<String> {
+        self.sfout.try_recv().ok()
+    }
+
+    fn is_alive(&mut self) -> bool {
+        matches!(self.child.try_wait(), Ok(None))
+    }
+
+    fn kill(&mut self) {
+        let _ = self.child.kill();
+    }
+
+    fn close(&mut self) {
+        let _ = self.child.wait();
+    }
+
+    fn reconnect(&mut self) -> Result<Box<dyn Connection>, SFErrors> {
+        self.kill();
+        self.close();
+        Ok(Box::new(Process::new(&self.path)?))
+    }
+
+    fn id(&self) -> Option<u32> {
+        Some(self.child.id())
+    }
+}
+
+pub struct Engine { // uci engine which lives for the whole game, so the hash is kept between moves, gets restarted if it died
+    pub path: String,
+    pub name: String,   // from "id name", e.g. "Stockfish 17"
+    pub author: String,
+    pub options: Vec<UciOption>,    // options the engine offers
+    set_options: Vec<(String, OptionValue)>,    // options set with set_option(), sent again after a restart
+    conn: Box<dyn Connection>,
+    elo: Option<u32>,   // strength which is set at the moment, None for full strength
+    chess960: bool,
+    multipv: u32,   // number of lines the engine searches at the moment
+    new_game: bool, // ucinewgame is sent before the next search
+    searching: bool,    // go was sent and bestmove didn't come yet
+    pondering: bool,    // the search runs on the expected reply until ponderhit or stop
+    timeout: Option<Duration>,  // of the running search, see SearchLimits
+    deadline: Option<Instant>   // when the running search gets stopped, None while pondering
+}
+
+impl std::fmt::Debug for Engine {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("Engine").field("path", &self.path).field("name", &self.name).field("pid", &self.conn.id()).field("elo", &self.elo).field("chess960", &self.chess960).finish()
+    }
+}
+
+#[derive(Debug)]
+#[derive(Clone, PartialEq, Eq)]
+pub enum OptionType {   // types of uci options with their limits
+    Check(bool),    // default
+    Spin(i64, i64, i64),    // default, min, max
+    Combo(String, Vec<String>), // default, possible values
+    Button,
+    String(String)  // default
+}
+
+#[derive(Debug)]
+#[derive(Clone, PartialEq, Eq)]
+pub struct UciOption {  // option from an "option name ... type ..." line
+    pub name: String,
+    pub kind: OptionType
+}
+
+#[derive(Debug)]
+#[derive(Clone, PartialEq, Eq)]
+pub enum OptionValue {  // value for setting an option, has to fit its type
+    Check(bool),
+    Spin(i64),
+    Combo(String),
+    Button,
+    String(String)
+}
+
+#[derive(Debug)]
+#[derive(Clone, Default, PartialEq, Eq)]
+pub struct SearchLimits {   // parameters of go, times in milliseconds, limits which are None aren't sent
+    pub movetime: Option<u32>,
+    pub depth: Option<u32>,
+    pub nodes: Option<u64>,
+    pub wtime: Option<u32>, // remaining clock times, the engine manages its time itself
+    pub btime: Option<u32>,
+    pub winc: Option<u32>,
+    pub binc: Option<u32>,
+    pub movestogo: Option<u32>, // moves until the next time control
+    pub infinite: bool, // searches until stop() is called
+    pub ponder: bool    // searches on the expected reply, the limits count from ponderhit, see Engine::go_ponder()
+}
+
+impl SearchLimits {
+    pub fn movetime(time: u32) -> Self {
+        SearchLimits { movetime: Some(time), ..Default::default() }
+    }
+
+    pub fn depth(depth: u32) -> Self {
+        SearchLimits { depth: Some(depth), ..Default::default() }
+    }
+
+    pub fn nodes(nodes: u64) -> Self {
+        SearchLimits { nodes: Some(nodes), ..Default::default() }
+    }
+
+    pub fn clock(wtime: u32, btime: u32, winc: u32, binc: u32) -> Self {
+        SearchLimits { wtime: Some(wtime), btime: Some(btime), winc: Some(winc), binc: Some(binc), ..Default::default() }
+    }
+
+    pub fn infinite() -> Self {
+        SearchLimits { infinite: true, ..Default::default() }
+    }
+
+    pub fn is_infinite(&self) -> bool { // no limit at all also means searching until stop
+        self.infinite || *self == SearchLimits::default()
+    }
+
+    pub fn to_go(&self) -> String { // go command without newline, e.g. "go wtime 60000 btime 60000 winc 1000 binc 1000"
+        let mut res = String::from("go");
+        if self.ponder {
+            res.push_str(" ponder");
+        };
+        let fields: [(&str, Option<u64>); 8] = [
+            ("wtime", self.wtime.map(u64::from)),
+            ("btime", self.btime.map(u64::from)),
+            ("winc", self.winc.map(u64::from)),
+            ("binc", self.binc.map(u64::from)),
+            ("movestogo", self.movestogo.map(u64::from)),
+            ("depth", self.depth.map(u64::from)),
+            ("nodes", self.nodes),
+            ("movetime", self.movetime.map(u64::from))
+        ];
+        for (name, value) in fields {
+            if let Some(v) = value {
+                res.push_str(&format!(" {} {}", name, v));
+            };
+        };
+        if self.infinite {
+            res.push_str(" infinite");
+        };
+        res
+    }
+
+    fn timeout(&self, white: bool) -> Option<Duration> {  // time after which the search gets stopped, None if only depth or nodes limit it
+        let clock = if white {self.wtime} else {self.btime};
+        [self.movetime, clock].into_iter().flatten().min().map(|t| Duration::from_millis(t as u64) + SEARCH_MARGIN)
+    }
+}
+
+#[derive(Debug)]
+#[derive(Clone, Default, PartialEq, Eq)]
+pub struct SearchOutput {   // result of one search
+    pub bestmove: Option<String>,   // None if there is no legal move ("bestmove (none)" or "0000")
+    pub ponder: Option<String>,
+    pub infos: Vec<SearchInfo>  // all info lines in the order they came
+}
+
+impl SearchOutput {
+    pub fn score(&self) -> Option<SFScore> {    // last score of the main line
+        self.infos.iter().rev().filter(|i| i.multipv.unwrap_or(1) == 1).find_map(|i| i.score)
+    }
+
+    pub fn result(&self) -> Result<SFResults, SFErrors> {   // the move, or why there is none
+        match (&self.bestmove, self.score()) {
+            (Some(mv), _) => Ok(SFResults::Normal(mv.clone())),
+            (None, Some(SFScore::Mate(_))) => Ok(SFResults::Mate),  // no legal move, "score mate 0" if in check
+            (None, Some(SFScore::Centipawns(_))) => Ok(SFResults::Stalemate),
+            (None, None) => Err(SFErrors::SFProcessing)
+        }
+    }
+
+    pub fn lines(&self) -> Vec<SearchInfo> {    // last complete info of every line with score and pv, best line first
+        let mut res: Vec<SearchInfo> = Vec::new();
+        for info in self.infos.iter().filter(|i| i.score.is_some() && !i.pv.is_empty()) {
+            let n = info.multipv.unwrap_or(1);
+            match res.iter_mut().find(|l| l.multipv.unwrap_or(1) == n) {
+                Some(l) => *l = info.clone(),
+                None => res.push(info.clone())
+            };
+        };
+        res.sort_by_key(|l| l.multipv.unwrap_or(1));
+        res
+    }
+}
+
+#[derive(Debug)]
+#[derive(Clone, Default, PartialEq, Eq)]
+pub struct SearchInfo { // contents of an info line, fields the engine didn't send are None
+    pub depth: Option<u32>,
+    pub seldepth: Option<u32>,
+    pub multipv: Option<u32>,
+    pub score: Option<SFScore>,
+    pub bound: Option<bool>,    // Some(true) for a lowerbound, Some(false) for an upperbound
+    pub nodes: Option<u64>,
+    pub nps: Option<u64>,
+    pub time: Option<u64>,  // milliseconds
+    pub pv: Vec<String>,
+    pub string: Option<String>  // free text after "info string"
+}
+
+impl Engine {
+    pub fn new(path: &str) -> Result<Self, SFErrors> {    // starts the engine at the given path and does the uci handshake
+        let mut res = Engine::with_connection(Box::new(Process::new(path)?))?;
+        res.path = path.to_string();
+        Ok(res)
+    }
+
+    pub fn discover() -> Result<Self, SFErrors> {  // starts the engine found by find_engine() and sets the options of the config file
+        let config = match EngineConfig::load() {
+            Ok(c) => c,
+            Err(rr) => return Err(SFErrors::Config(rr))
+        };
+        let path = match find_engine(&config) {
+            Some(p) => p,
+            None => return Err(SFErrors::NotFound)
+        };
+        let mut res = Engine::new(&path)?;
+        res.configure(&config)?;
+        Ok(res)
+    }
+
+    pub fn configure(&mut self, config: &EngineConfig) -> Result<(), SFErrors> {    // sets the options of the config, options this engine doesn't offer are left out
+        for (name, value) in config.options() {
+            if self.has_option(name) {
+                self.set_option(name, value)?;
+            };
+        };
+        Ok(())
+    }
+
+    pub fn version(&self) -> Option<&str> {  // version from the name, e.g. "17.1" of "Stockfish 17.1" or "dev-20260426-1a882efc"
+        parse_id_name(&self.name).1
+    }
+
+    pub fn is_stockfish(&self) -> bool {
+        parse_id_name(&self.name).0.eq_ignore_ascii_case("stockfish")
+    }
+
+    pub fn with_connection(conn: Box<dyn Connection>) -> Result<Self, SFErrors> {   // uci handshake over any connection, e.g. a mock::MockEngine
+        let mut res = Engine { path: String::new(), name: String::new(), author: String::new(), options: Vec::new(), set_options: Vec::new(), conn, elo: None, chess960: false, multipv: 1, new_game: false, searching: false, pondering: false, timeout: None, deadline: None };
+
+        // engine tells its name and options, anything before (like a banner) is skipped
+        res.send("uci\n")?;
+        for line in res.read_until("uciok", Some(HANDSHAKE_TIMEOUT))?.iter() {
+            let wlist: Vec<&str> = line.split_whitespace().collect();
+            match wlist.as_slice() {
+                ["id", "name", rest @ ..] => res.name = rest.join(" "),
+                ["id", "author", rest @ ..] => res.author = rest.join(" "),
+                ["option", ..] => res.options.extend(parse_option(line)),
+                _ => {}
+            }
+        };
+        if res.has_option("UCI_LimitStrength") {
+            res.send("setoption name UCI_LimitStrength value false\n")?;
+        };
+        res.send("isready\n")?;
+        res.read_until("readyok", Some(HANDSHAKE_TIMEOUT))?;
+        Ok(res)
+    }
+
+    pub fn option(&self, name: &str) -> Option<&UciOption> {    // option by name, case is ignored like in the uci protocol
+        self.options.iter().find(|o| o.name.eq_ignore_ascii_case(name))
+    }
+
+    pub fn has_option(&self, name: &str) -> bool {
+        self.option(name).is_some()
+    }
+
+    pub fn set_option(&mut self, name: &str, value: OptionValue) -> Result<(), SFErrors> {  // checks the value against the option and sends it, it is kept for restarts
+        let option = match self.option(name) {
+            Some(o) => o.clone(),
+            None => return Err(SFErrors::UnknownOption(name.to_string()))
+        };
+        let text = match (&option.kind, &value) {
+            (OptionType::Check(_), OptionValue::Check(b)) => Some(b.to_string()),
+            (OptionType::Spin(_, min, max), OptionValue::Spin(v)) if (*min..=*max).contains(v) => Some(v.to_string()),
+            (OptionType::Combo(_, vars), OptionValue::Combo(v)) if vars.iter().any(|x| x.eq_ignore_ascii_case(v)) => Some(v.clone()),
+            (OptionType::String(_), OptionValue::String(v)) => Some(v.clone()),
+            (OptionType::Button, OptionValue::Button) => None,
+            _ => return Err(SFErrors::InvalidOption(option.name))
+        };
+        match text {
+            Some(t) => self.send(&format!("setoption name {} value {}\n", option.name, t))?,
+            None => self.send(&format!("setoption name {}\n", option.name))?
+        };
+        self.set_options.retain(|(n, _)| *n != option.name);
+        if value != OptionValue::Button {
+            self.set_options.push((option.name, value));
+        };
+        Ok(())
+    }
+
+    pub fn get_move(&mut self, fen: &str, elo: u32, time: u32) -> Result<SFResults, SFErrors> {  // best move with the given strength
+        self.get_move_with(fen, Some(elo), &SearchLimits::movetime(time))
+    }
+
+    pub fn get_move_with(&mut self, fen: &str, elo: Option<u32>, limits: &SearchLimits) -> Result<SFResults, SFErrors> {    // best move with the given strength and limits, None for full strength
+        self.search(fen, elo, limits)?.result()
+    }
+
+    pub fn get_eval(&mut self, fen: &str, time: u32) -> Result<SFScore, SFErrors> { // evaluates the position at full strength, the score is seen from the side to move
+        match self.search(fen, None, &SearchLimits::movetime(time))?.score() {
+            Some(score) => Ok(score),
+            None => Err(SFErrors::SFProcessing)
+        }
+    }
+
+    pub fn analyse(&mut self, fen: &str, lines: u32, limits: &SearchLimits) -> Result<Vec<SearchInfo>, SFErrors> {  // the best lines at full strength (multipv), engines without MultiPV give only one
+        Ok(self.search_lines(fen, None, lines, limits)?.lines())
+    }
+
+    pub fn new_game(&mut self) {    // the hash gets cleared before the next search
+        self.new_game = true;
+    }
+
+    pub fn search(&mut self, fen: &str, elo: Option<u32>, limits: &SearchLimits) -> Result<SearchOutput, SFErrors> {  // searches the position and waits for the result
+        self.search_lines(fen, elo, 1, limits)
+    }
+
+    fn search_lines(&mut self, fen: &str, elo: Option<u32>, lines: u32, limits: &SearchLimits) -> Result<SearchOutput, SFErrors> {  // sf is restarted once if it died
+        if limits.is_infinite() {
+            return Err(SFErrors::InfiniteSearch)
+        };
+        match self.search_once(fen, elo, lines, limits) {
+            Err(SFErrors::SFInWriting(_) | SFErrors::SFDied) if !self.is_alive() => {
+                self.restart()?;
+                self.search_once(fen, elo, lines, limits)
+            },
+            res => res
+        }
+    }
+
+    fn search_once(&mut self, fen: &str, elo: Option<u32>, lines: u32, limits: &SearchLimits) -> Result<SearchOutput, SFErrors> {
+        self.start(fen, &[], elo, lines, limits)?;
+        self.wait()
+    }
+
+    pub fn go(&mut self, fen: &str, moves: &[&str], elo: Option<u32>, limits: &SearchLimits) -> Result<(), SFErrors> {  // starts a search without waiting, the moves played since fen let the engine see repetitions, the result comes with wait() or stop()
+        self.start(fen, moves, elo, 1, limits)
+    }
+
+    pub fn go_ponder(&mut self, fen: &str, moves: &[&str], ponder_move: &str, elo: Option<u32>, limits: &SearchLimits) -> Result<(), SFErrors> {   // thinks on the expected reply while the opponent is to move, then ponderhit() or stop()
+        let limits = SearchLimits { ponder: true, ..limits.clone() };
+        let moves: Vec<&str> = moves.iter().copied().chain([ponder_move]).collect();
+        self.start(fen, &moves, elo, 1, &limits)
+    }
+
+    pub fn ponderhit(&mut self) -> Result<(), SFErrors> {   // the opponent played the expected move, the search goes on as a normal one
+        if !self.pondering {
+            return Err(SFErrors::NotPondering)
+        };
+        self.send("ponderhit\n")?;
+        self.pondering = false;
+        self.deadline = self.timeout.map(|t| Instant::now() + t);
+        Ok(())
+    }
+
+    pub fn is_searching(&self) -> bool {
+        self.searching
+    }
+
+    pub fn is_pondering(&self) -> bool {
+        self.pondering
+    }
+
+    pub fn wait(&mut self) -> Result<SearchOutput, SFErrors> {  // result of the running search, it is stopped when its time is over
+        if !self.searching {
+            return Err(SFErrors::NotSearching)
+        };
+        if self.pondering {
+            return Err(SFErrors::Pondering)
+        };
+        let timeout = self.deadline.map(|d| d.saturating_duration_since(Instant::now()));
+        let lines = match self.read_until("bestmove", timeout) {
+            Err(SFErrors::Timeout) => return self.stop(),   // engine is too slow, it gets one more chance to answer
+            res => res?
+        };
+        self.searching = false;
+        Ok(parse_search(&lines))
+    }
+
+    fn start(&mut self, fen: &str, moves: &[&str], elo: Option<u32>, lines: u32, limits: &SearchLimits) -> Result<(), SFErrors> {  // options are only sent if they changed
+        if self.searching {
+            let _ = self.stop();
+        };
+        if !self.is_alive() {
+            self.restart()?;
+        };
+        while self.conn.try_read_line().is_some() {}    // left over output, e.g. of a search which timed out
+        let mut commands = String::new();
+        if self.new_game {
+            commands.push_str("ucinewgame\n");
+            self.new_game = false;
+        };
+        let chess960 = is_chess960(fen);
+        if chess960 != self.chess960 && self.has_option("UCI_Chess960") {
+            commands.push_str(&format!("setoption name UCI_Chess960 value {}\n", chess960));
+            self.chess960 = chess960;
+        };
+        if elo != self.elo && self.has_option("UCI_LimitStrength") && self.has_option("UCI_Elo") {   // engines without these always play at full strength
+            let elo = match (elo, self.option("UCI_Elo").map(|o| &o.kind)) {
+                (Some(e), Some(OptionType::Spin(_, min, max))) => Some((e as i64).clamp(*min, *max) as u32),
+                (e, _) => e
+            };
+            match elo {
+                Some(e) => commands.push_str(&format!("setoption name UCI_LimitStrength value true\nsetoption name UCI_Elo value {}\n", e)),
+                None => commands.push_str("setoption name UCI_LimitStrength value false\n")
+            };
+            self.elo = elo;
+        };
+        let lines = match self.option("MultiPV").map(|o| &o.kind) {
+            Some(OptionType::Spin(_, min, max)) => (lines as i64).clamp(*min, *max) as u32,
+            _ => 1
+        };
+        if lines != self.multipv {
+            commands.push_str(&format!("setoption name MultiPV value {}\n", lines));
+            self.multipv = lines;
+        };
+        let position = match moves {
+            [] => format!("position fen {}", fen),
+            _ => format!("position fen {} moves {}", fen, moves.join(" "))
+        };
+        self.send(&format!("{}{}\n{}\n", commands, position, limits.to_go()))?;
+        let white = (fen.split_whitespace().nth(1) != Some("b")) ^ (moves.len() % 2 == 1);
+        self.searching = true;
+        self.pondering = limits.ponder;
+        self.timeout = limits.timeout(white);
+        self.deadline = if limits.ponder {None} else {self.timeout.map(|t| Instant::now() + t)};
+        Ok(())
+    }
+
+    pub fn stop(&mut self) -> Result<SearchOutput, SFErrors> {  // ends the running search and gives back its result, an engine which doesn't answer gets killed and is restarted next time
+        if !self.searching {
+            return Err(SFErrors::NotSearching)
+        };
+        self.searching = false;
+        self.pondering = false;
+        self.send("stop\n")?;
+        match self.read_until("bestmove", Some(STOP_TIMEOUT)) {
+            Ok(lines) => Ok(parse_search(&lines)),
+            Err(rr) => {
+                self.conn.kill();
+                Err(rr)
+            }
+        }
+    }
+
+    fn is_alive(&mut self) -> bool {
+        self.conn.is_alive()
+    }
+
+    fn restart(&mut self) -> Result<(), SFErrors> { // replaces the process by a new one, options set by the user are sent again, the others at the next search
+        let conn = self.conn.reconnect()?;
+        let set_options = std::mem::take(&mut self.set_options);
+        let path = std::mem::take(&mut self.path);
+        *self = Engine::with_connection(conn)?;
+        self.path = path;
+        for (name, value) in set_options {
+            self.set_option(&name, value)?;
+        };
+        Ok(())
+    }
+
+    fn send(&mut self, commands: &str) -> Result<(), SFErrors> {
+        self.conn.send(commands)
+    }
+
+    fn read_until(&mut self, first_word: &str, timeout: Option<Duration>) -> Result<Vec<String>, SFErrors> {   // reads the output up to and including the line which starts with the given word, None waits as long as it takes
+        let deadline = timeout.map(|t| Instant::now() + t);
+        let mut res = Vec::new();
+        loop {
+            let line = self.conn.read_line(deadline.map(|d| d.saturating_duration_since(Instant::now())))?;
+            let done = line.split_whitespace().next() == Some(first_word);
+            res.push(line);
+            if done {
+                return Ok(res)
+            };
         }
     }
 }
 
+impl Drop for Engine {
+    fn drop(&mut self) {
+        let _ = self.send("quit\n");
+        self.conn.close();
+    }
+}
+
+pub fn parse_id_name(name: &str) -> (&str, Option<&str>) {   // engine family and version of an "id name" line, e.g. "Stockfish 17.1" => ("Stockfish", Some("17.1"))
+    let mut words = name.split_whitespace();
+    let family = words.next().unwrap_or("");
+    let version = words.next().filter(|v| v.starts_with(|c: char| c.is_ascii_digit()) || v.starts_with("dev"));
+    (family, version)
+}
+
+fn is_chess960(fen: &str) -> bool {  // shredder fen (rochade rights as columns of the rooks) needs the chess960 mode of sf
+    match fen.split_whitespace().nth(2) {
+        Some(r) => r.chars().any(|c| !"KQkq-".contains(c)),
+        None => false
+    }
+}
+
+fn parse_search(lines: &[String]) -> SearchOutput {    // collects infos and the bestmove of a search
+    let mut res = SearchOutput::default();
+    for line in lines {
+        if let Some(info) = parse_info(line) {
+            res.infos.push(info);
+        } else if let Some((bestmove, ponder)) = parse_bestmove(line) {
+            res.bestmove = bestmove;
+            res.ponder = ponder;
+        };
+    };
+    res
+}
+
+pub fn parse_bestmove(line: &str) -> Option<(Option<String>, Option<String>)> {  // move and ponder move of a bestmove line, the move is None if there is no legal move
+    let wlist: Vec<&str> = line.split_whitespace().collect();
+    let coord = |w: Option<&&str>| w.filter(|m| is_coordinates(m)).map(|m| m.to_string());
+    match wlist.as_slice() {
+        ["bestmove", rest @ ..] => {
+            let ponder = match rest.iter().position(|w| *w == "ponder") {
+                Some(i) => coord(rest.get(i + 1)),
+                None => None
+            };
+            Some((coord(rest.first()), ponder))
+        },
+        _ => None
+    }
+}
+
+fn is_coordinates(mv: &str) -> bool {   // move in coordinate notation, e.g. e2e4 or e7e8q
+    let b = mv.as_bytes();
+    (b.len() == 4 || (b.len() == 5 && b"qrbn".contains(&b[4])))
+        && (b'a'..=b'h').contains(&b[0]) && (b'1'..=b'8').contains(&b[1])
+        && (b'a'..=b'h').contains(&b[2]) && (b'1'..=b'8').contains(&b[3])
+}
+
+pub fn parse_score(line: &str) -> Option<SFScore> {   // extracts the score of an info line, e.g. "info depth 20 ... score cp -35 nodes ..."
+    parse_info(line)?.score
+}
+
+pub fn parse_info(line: &str) -> Option<SearchInfo> {   // parses an info line, unknown fields are skipped, None if it is no info line
+    let wlist: Vec<&str> = line.split_whitespace().collect();
+    if wlist.first() != Some(&"info") {
+        return None
+    };
+    let mut res = SearchInfo::default();
+    let mut i = 1;
+    while i < wlist.len() {
+        let next = wlist.get(i + 1).copied().unwrap_or("");
+        match wlist[i] {
+            "depth" => res.depth = next.parse().ok(),
+            "seldepth" => res.seldepth = next.parse().ok(),
+            "multipv" => res.multipv = next.parse().ok(),
+            "nodes" => res.nodes = next.parse().ok(),
+            "nps" => res.nps = next.parse().ok(),
+            "time" => res.time = next.parse().ok(),
+            "score" => {
+                res.score = match (next, wlist.get(i + 2).map(|v| v.parse::<i32>())) {
+                    ("cp", Some(Ok(v))) => Some(SFScore::Centipawns(v)),
+                    ("mate", Some(Ok(v))) => Some(SFScore::Mate(v)),
+                    _ => None
+                };
+                i += 1;
+                match wlist.get(i + 2) {
+                    Some(&"lowerbound") => { res.bound = Some(true); i += 1 },
+                    Some(&"upperbound") => { res.bound = Some(false); i += 1 },
+                    _ => {}
+                };
+            },
+            "pv" => {
+                res.pv = wlist[i + 1..].iter().map(|m| m.to_string()).collect();
+                break
+            },
+            "string" => {
+                res.string = Some(wlist[i + 1..].join(" "));
+                break
+            },
+            _ => {
+                i += 1;
+                continue
+            }
+        };
+        i += 2;
+    };
+    Some(res)
+}
+
+pub fn parse_option(line: &str) -> Option<UciOption> { // parses an option line, e.g. "option name Hash type spin default 16 min 1 max 33554432"
+    let wlist: Vec<&str> = line.split_whitespace().collect();
+    if wlist.first() != Some(&"option") {
+        return None
+    };
+    let mut parts: Vec<(&str, Vec<&str>)> = Vec::new();    // keyword and the words up to the next keyword, names and values may contain spaces
+    for w in &wlist[1..] {
+        match *w {
+            "name" | "type" | "default" | "min" | "max" | "var" => parts.push((w, Vec::new())),
+            _ => match parts.last_mut() {
+                Some((_, words)) => words.push(w),
+                None => return None
+            }
+        }
+    };
+    let get = |key: &str| parts.iter().find(|(k, _)| *k == key).map(|(_, words)| words.join(" "));
+    let number = |key: &str| get(key).and_then(|v| v.parse::<i64>().ok());
+    let name = get("name").filter(|n| !n.is_empty())?;
+    let default = get("default").filter(|d| d != "<empty>").unwrap_or_default();
+    let kind = match get("type")?.as_str() {
+        "check" => OptionType::Check(default == "true"),
+        "spin" => OptionType::Spin(number("default")?, number("min")?, number("max")?),
+        "combo" => OptionType::Combo(default, parts.iter().filter(|(k, _)| *k == "var").map(|(_, words)| words.join(" ")).collect()),
+        "button" => OptionType::Button,
+        "string" => OptionType::String(default),
+        _ => return None
+    };
+    Some(UciOption { name, kind })
+}
+
 #[derive(Debug)]
 pub enum SFErrors { // Errortypes related to stockfish sf
     CreationError(Error),   // creation of sf child failed
     SFInCreation,   // creation of ip handle failed
     SFOutCreation,  // creation of op handle failed
-    SFTesting,  // test sequence resulted in unforseen op
+    SFTesting,  // engine didn't answer the uci handshake as exspected
     SFInWriting(Error), // writing in sf ip failed
-    SFOutReading(Error),    // writing in sf op failed
-    SFOutReadingParsing(FromUtf8Error), // failed to parse op to string
     SFProcessing,   // result of sf can't be processed as exspected
+    SFDied,  // output of sf ended unexspectedly
+    Timeout,    // engine didn't answer in time
+    InfiniteSearch, // search() needs a limit, infinite searches are started with go() and ended with stop()
+    NotSearching,   // stop() or wait() without a running search
+    Pondering,  // wait() while pondering would never end, ponderhit() or stop() first
+    NotPondering,   // ponderhit() without go_ponder()
+    UnknownOption(String),  // engine doesn't offer an option with this name
+    InvalidOption(String),  // value doesn't fit the type or the limits of the option
+    NotFound,   // no engine found, see find_engine()
+    Config(ConfigError) // config file can't be read
 }
 
 #[derive(Debug)]
@@ -142,8 +904,15 @@ pub enum SFResults {    // all possible types of sf outputs
     Mate
 }
 
-fn new_sf() -> Result<Child, SFErrors> {    // creates new sf child process which is used by get_move()
-    match Command::new(PATH_TO_STOCKFISH)
+#[derive(Debug)]
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub enum SFScore {  // evaluation of a position from the view of the side to move
+    Centipawns(i32),
+    Mate(i32)   // mate in n moves, negative if the side to move gets mated
+}
+
+fn new_sf(path: &str) -> Result<Child, SFErrors> {    // creates new engine child process which is used by Engine
+    match Command::new(path)
         .stdin(Stdio::piped())
         .stdout(Stdio::piped())
         .spawn()
@@ -153,26 +922,355 @@ fn new_sf() -> Result<Child, SFErrors> {    // creates new sf child process whic
     }
 }
 
-#[cfg(test)]    // tests all three types of possible moves, see SFResults
+pub mod mock {  // scripted uci engine for tests, answers commands with canned lines and needs no engine binary
+    use std::{collections::VecDeque, io::{Error, ErrorKind}, sync::{Arc, Mutex}, time::Duration};
+    use super::{Connection, SFErrors};
+
+    #[derive(Debug)]
+    #[derive(Clone, PartialEq, Eq)]
+    enum Reply {
+        Lines(Vec<String>),
+        Die // the engine crashes when it gets the command
+    }
+
+    #[derive(Debug)]
+    #[derive(Clone)]
+    pub struct MockEngine {
+        header: Vec<String>,    // answer to uci without uciok
+        replies: Vec<(String, VecDeque<Reply>)>,    // replies for commands with the given first word, used in order, the last one stays
+        output: VecDeque<String>,
+        alive: bool,
+        log: Arc<Mutex<Vec<String>>>   // every command line the engine got, shared with reconnected engines
+    }
+
+    impl MockEngine {
+        pub fn new() -> Self {  // engine with the usual options of sf, answers uci and isready, nothing else
+            let header = [
+                "id name Mock",
+                "id author chess_firmware",
+                "option name Threads type spin default 1 min 1 max 1024",
+                "option name Hash type spin default 16 min 1 max 33554432",
+                "option name SyzygyPath type string default <empty>",
+                "option name MultiPV type spin default 1 min 1 max 500",
+                "option name UCI_Chess960 type check default false",
+                "option name UCI_LimitStrength type check default false",
+                "option name UCI_Elo type spin default 1320 min 1320 max 3190",
+                "option name Skill Level type spin default 20 min 0 max 20"
+            ];
+            MockEngine { header: header.iter().map(|l| l.to_string()).collect(), replies: Vec::new(), output: VecDeque::new(), alive: true, log: Arc::new(Mutex::new(Vec::new())) }
+        }
+
+        pub fn on(mut self, command: &str, lines: &[&str]) -> Self {    // adds an answer to the next command starting with the given word, e.g. on("go", &["bestmove e2e4"])
+            let reply = Reply::Lines(lines.iter().map(|l| l.to_string()).collect());
+            self.push_reply(command, reply);
+            self
+        }
+
+        pub fn dies_on(mut self, command: &str) -> Self {   // the engine crashes at the next command starting with the given word
+            self.push_reply(command, Reply::Die);
+            self
+        }
+
+        pub fn log(&self) -> Arc<Mutex<Vec<String>>> {  // commands the engine got, also after restarts
+            Arc::clone(&self.log)
+        }
+
+        fn push_reply(&mut self, command: &str, reply: Reply) {
+            match self.replies.iter_mut().find(|(c, _)| c == command) {
+                Some((_, list)) => list.push_back(reply),
+                None => self.replies.push((command.to_string(), VecDeque::from([reply])))
+            };
+        }
+
+        fn reply(&mut self, command: &str) -> Option<Reply> {
+            let list = &mut self.replies.iter_mut().find(|(c, _)| c == command)?.1;
+            if list.len() > 1 {
+                list.pop_front()
+            } else {
+                list.front().cloned()
+            }
+        }
+    }
+
+    impl Default for MockEngine {
+        fn default() -> Self {
+            Self::new()
+        }
+    }
+
+    impl Connection for MockEngine {
+        fn send(&mut self, commands: &str) -> Result<(), SFErrors> {
+            for line in commands.lines().filter(|l| !l.trim().is_empty()) {
+                if !self.alive {
+                    return Err(SFErrors::SFInWriting(Error::new(ErrorKind::BrokenPipe, "mock engine died")))
+                };
+                if let Ok(mut log) = self.log.lock() {
+                    log.push(line.to_string());
+                };
+                let word = line.split_whitespace().next().unwrap_or("");
+                match (word, self.reply(word)) {
+                    (_, Some(Reply::Die)) => {
+                        self.alive = false;
+                        self.output.clear();
+                    },
+                    (_, Some(Reply::Lines(lines))) => self.output.extend(lines),
+                    ("uci", None) => {
+                        let header = self.header.clone();
+                        self.output.extend(header);
+                        self.output.push_back(String::from("uciok"));
+                    },
+                    ("isready", None) => self.output.push_back(String::from("readyok")),
+                    _ => {}
+                };
+            };
+            Ok(())
+        }
+
+        fn read_line(&mut self, _timeout: Option<Duration>) -> Result<String, SFErrors> {   // no waiting, without output the engine is just too slow
+            match self.output.pop_front() {
+                Some(line) => Ok(line),
+                None if self.alive => Err(SFErrors::Timeout),
+                None => Err(SFErrors::SFDied)
+            }
+        }
+
+        fn try_read_line(&mut self) -> Option<String> {
+            self.output.pop_front()
+        }
+
+        fn is_alive(&mut self) -> bool {
+            self.alive
+        }
+
+        fn kill(&mut self) {
+            self.alive = false;
+            self.output.clear();
+        }
+
+        fn close(&mut self) {}
+
+        fn reconnect(&mut self) -> Result<Box<dyn Connection>, SFErrors> {  // goes on with the remaining replies
+            let mut res = self.clone();
+            res.alive = true;
+            res.output.clear();
+            Ok(Box::new(res))
+        }
+    }
+}
+
+#[cfg(test)]    // tests all three types of possible moves, see SFResults, with the real engine (ignored by default) and with the mock engine
 mod tests {
 
-    use super::{get_move, SFResults};
+    use super::{get_move, mock::MockEngine, parse_bestmove, parse_id_name, Engine, EngineConfig, OptionValue, SearchLimits, SearchOutput, SFErrors, parse_info, parse_option, parse_score, OptionType, SFResults, SFScore, UciOption};
 
     #[test]
+    #[ignore = "needs the engine binary in sfs"]
     fn it_works() {
         let result = get_move("8/1q6/5k2/K7/Pp6/8/8/8 w - - 1 54", 3000, 1000);
         assert_eq!(result.unwrap(), SFResults::Stalemate);
     }
 
     #[test]
+    #[ignore = "needs the engine binary in sfs"]
     fn it_works2() {
         let result = get_move("8/3R4/3kp3/1Q6/1P6/8/1PK1P3/8 b - - 8 44", 3000, 1000);
         assert_eq!(result.unwrap(), SFResults::Mate);
     }
 
     #[test]
+    #[ignore = "needs the engine binary in sfs"]
     fn it_works3() {
         let result = get_move("1R5k/5ppp/5n2/8/8/5K2/8/8 b - - 0 1", 3000, 1000);
         assert_eq!(result.unwrap(), SFResults::Normal("f6g8".to_string()));
     }
-}
\ No newline at end of file
+
+    #[test]
+    fn it_works4() {
+        assert_eq!(parse_score("info depth 18 seldepth 24 multipv 1 score cp -35 nodes 1000 nps 1000 pv e7e5"), Some(SFScore::Centipawns(-35)));
+        assert_eq!(parse_score("info depth 5 score mate 3 pv d1h5"), Some(SFScore::Mate(3)));
+        assert_eq!(parse_score("info string NNUE evaluation using nn.nnue"), None);
+        assert_eq!(parse_score("bestmove e2e4 ponder e7e5"), None);
+    }
+
+    #[test]
+    fn it_works5() {
+        let info = parse_info("info depth 20 seldepth 28 multipv 2 score cp 31 lowerbound nodes 123456 nps 987654 hashfull 12 tbhits 0 time 125 pv e2e4 e7e5 g1f3").unwrap();
+        assert_eq!((info.depth, info.seldepth, info.multipv), (Some(20), Some(28), Some(2)));
+        assert_eq!((info.score, info.bound), (Some(SFScore::Centipawns(31)), Some(true)));
+        assert_eq!((info.nodes, info.nps, info.time), (Some(123456), Some(987654), Some(125)));
+        assert_eq!(info.pv, vec!["e2e4", "e7e5", "g1f3"]);
+        assert_eq!(parse_info("info string NNUE evaluation using nn.nnue").unwrap().string.as_deref(), Some("NNUE evaluation using nn.nnue"));
+        assert_eq!(parse_info("info depth 1 seldepth 1 score mate -2 pv a1a2").unwrap().score, Some(SFScore::Mate(-2)));
+        assert!(parse_info("id name Stockfish 17").is_none());
+
+        assert_eq!(parse_option("option name Hash type spin default 16 min 1 max 33554432"), Some(UciOption { name: String::from("Hash"), kind: OptionType::Spin(16, 1, 33554432) }));
+        assert_eq!(parse_option("option name Clear Hash type button"), Some(UciOption { name: String::from("Clear Hash"), kind: OptionType::Button }));
+        assert_eq!(parse_option("option name UCI_Chess960 type check default false").unwrap().kind, OptionType::Check(false));
+        assert_eq!(parse_option("option name SyzygyPath type string default <empty>").unwrap().kind, OptionType::String(String::new()));
+        assert_eq!(parse_option("option name Backend type combo default cpu var cpu var cuda fp16").unwrap().kind, OptionType::Combo(String::from("cpu"), vec![String::from("cpu"), String::from("cuda fp16")]));
+        assert!(parse_option("option name Broken type spin default x").is_none());
+    }
+
+    #[test]
+    fn it_works6() {
+        assert_eq!(parse_bestmove("bestmove e2e4 ponder e7e5"), Some((Some(String::from("e2e4")), Some(String::from("e7e5")))));
+        assert_eq!(parse_bestmove("bestmove e7e8q"), Some((Some(String::from("e7e8q")), None)));
+        assert_eq!(parse_bestmove("bestmove (none)"), Some((None, None)));
+        assert_eq!(parse_bestmove("bestmove 0000"), Some((None, None)));
+        assert_eq!(parse_bestmove("info string bestmove e2e4"), None);
+    }
+
+    #[test]
+    fn it_works7() {
+        assert_eq!(SearchLimits::movetime(1000).to_go(), "go movetime 1000");
+        assert_eq!(SearchLimits::clock(60000, 55000, 1000, 1000).to_go(), "go wtime 60000 btime 55000 winc 1000 binc 1000");
+        assert_eq!(SearchLimits { depth: Some(12), nodes: Some(100000), ..Default::default() }.to_go(), "go depth 12 nodes 100000");
+        assert_eq!(SearchLimits::infinite().to_go(), "go infinite");
+        assert!(SearchLimits::default().is_infinite());
+        assert!(!SearchLimits::depth(5).is_infinite());
+    }
+
+    #[test]
+    fn it_works8() {
+        let lines = [
+            "info depth 10 seldepth 14 multipv 1 score cp 40 nodes 5000 nps 100000 time 50 pv e2e4 e7e5",
+            "info depth 10 seldepth 12 multipv 2 score cp 25 nodes 5000 nps 100000 time 50 pv d2d4",
+            "info depth 11 seldepth 15 multipv 1 score cp 35 lowerbound nodes 9000 pv e2e4",
+            "info depth 11 seldepth 16 multipv 2 score cp 30 nodes 12000 nps 100000 time 120 pv d2d4 d7d5 c2c4",
+            "info depth 11 seldepth 15 multipv 1 score cp 33 nodes 12000 nps 100000 time 120 pv e2e4 c7c5",
+            "info depth 11 currmove g1f3 currmovenumber 3",
+        ];
+        let output = SearchOutput { infos: lines.iter().filter_map(|l| parse_info(l)).collect(), ..Default::default() };
+        let res = output.lines();
+        assert_eq!(res.len(), 2);
+        assert_eq!((res[0].score, res[0].pv.join(" ")), (Some(SFScore::Centipawns(33)), String::from("e2e4 c7c5")));
+        assert_eq!((res[1].score, res[1].seldepth, res[1].pv.len()), (Some(SFScore::Centipawns(30)), Some(16), 3));
+        assert_eq!(output.score(), Some(SFScore::Centipawns(33)));
+    }
+
+    fn mock_engine(mock: MockEngine) -> Engine {
+        Engine::with_connection(Box::new(mock)).unwrap()
+    }
+
+    #[test]
+    fn it_works9() {
+        let mock = MockEngine::new()
+            .on("go", &["info depth 0 score cp 0", "bestmove (none)"])
+            .on("go", &["info depth 0 score mate 0", "bestmove (none)"])
+            .on("go", &["info depth 12 score cp 250 pv f6g8", "bestmove f6g8 ponder h7h6"]);
+        let log = mock.log();
+        let mut engine = mock_engine(mock);
+        assert_eq!(engine.name, "Mock");
+        assert_eq!(engine.get_move("8/1q6/5k2/K7/Pp6/8/8/8 w - - 1 54", 3000, 1000).unwrap(), SFResults::Stalemate);
+        assert_eq!(engine.get_move("8/3R4/3kp3/1Q6/1P6/8/1PK1P3/8 b - - 8 44", 3000, 1000).unwrap(), SFResults::Mate);
+        assert_eq!(engine.get_move("1R5k/5ppp/5n2/8/8/5K2/8/8 b - - 0 1", 3000, 1000).unwrap(), SFResults::Normal("f6g8".to_string()));
+        let log = log.lock().unwrap();
+        assert_eq!(log.iter().filter(|l| l.as_str() == "setoption name UCI_Elo value 3000").count(), 1);
+        assert_eq!(log.last().unwrap(), "go movetime 1000");
+    }
+
+    #[test]
+    fn it_works10() {
+        let mock = MockEngine::new()
+            .on("go", &["info depth 20 multipv 1 score cp -35 pv e7e5 g1f3", "info depth 20 multipv 2 score cp -40 pv c7c5", "bestmove e7e5"]);
+        let log = mock.log();
+        let mut engine = mock_engine(mock);
+        let lines = engine.analyse("rnbqkbnr/pppppppp/8/8/4P3/8/PPPP1PPP/RNBQKBNR b KQkq - 0 1", 2, &SearchLimits::depth(20)).unwrap();
+        assert_eq!(lines.len(), 2);
+        assert_eq!((lines[1].score, lines[1].pv.join(" ")), (Some(SFScore::Centipawns(-40)), String::from("c7c5")));
+        assert_eq!(engine.get_eval("rnbqkbnr/pppppppp/8/8/4P3/8/PPPP1PPP/RNBQKBNR b KQkq - 0 1", 100).unwrap(), SFScore::Centipawns(-35));
+        let log = log.lock().unwrap();
+        assert!(log.contains(&String::from("setoption name MultiPV value 2")));
+        assert!(log.contains(&String::from("setoption name MultiPV value 1")));
+    }
+
+    #[test]
+    fn it_works11() {   // engine doesn't answer in time, gets stopped, is killed if it doesn't react and started again
+        let mock = MockEngine::new()
+            .on("go", &[])
+            .on("go", &[])
+            .on("stop", &["bestmove d2d4"])
+            .on("stop", &[])
+            .on("go", &["bestmove c2c4"]);
+        let log = mock.log();
+        let mut engine = mock_engine(mock);
+        let fen = "rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 0 1";
+        assert_eq!(engine.get_move(fen, 1500, 10).unwrap(), SFResults::Normal("d2d4".to_string()));
+        assert!(matches!(engine.get_move(fen, 1500, 10), Err(SFErrors::Timeout)));
+        assert_eq!(engine.get_move(fen, 1500, 10).unwrap(), SFResults::Normal("c2c4".to_string()));
+        assert_eq!(log.lock().unwrap().iter().filter(|l| l.as_str() == "uci").count(), 2);
+    }
+
+    #[test]
+    fn it_works12() {   // engine crashes during the search, the search is repeated with a new one and the options are set again
+        let mock = MockEngine::new()
+            .dies_on("go")
+            .on("go", &["bestmove g1f3"]);
+        let log = mock.log();
+        let mut engine = mock_engine(mock);
+        engine.set_option("Hash", super::OptionValue::Spin(64)).unwrap();
+        assert!(matches!(engine.set_option("Hash", super::OptionValue::Spin(0)), Err(SFErrors::InvalidOption(_))));
+        assert_eq!(engine.get_move("rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 0 1", 1500, 10).unwrap(), SFResults::Normal("g1f3".to_string()));
+        let log = log.lock().unwrap();
+        assert_eq!(log.iter().filter(|l| l.as_str() == "uci").count(), 2);
+        assert_eq!(log.iter().filter(|l| l.as_str() == "setoption name Hash value 64").count(), 2);
+    }
+
+    #[test]
+    fn it_works13() {
+        let config = EngineConfig::parse("# comment\npath = /usr/games/stockfish\nThreads = 4\nhash=256\n\nSyzygyPath = /data/syzygy:/data/syzygy6\nSkill Level = 12\n").unwrap();
+        assert_eq!(config, EngineConfig { path: Some(String::from("/usr/games/stockfish")), threads: Some(4), hash: Some(256), syzygy_path: Some(String::from("/data/syzygy:/data/syzygy6")), skill_level: Some(12) });
+        assert_eq!(EngineConfig::parse(&config.to_text()).unwrap(), config);
+        assert!(EngineConfig::parse("Threads = many").is_err());
+        assert!(EngineConfig::parse("Ponder = true").is_err());
+        assert!(EngineConfig::parse("Threads 4").is_err());
+        assert_eq!(parse_id_name("Stockfish 17.1"), ("Stockfish", Some("17.1")));
+        assert_eq!(parse_id_name("Stockfish dev-20260426-1a882efc"), ("Stockfish", Some("dev-20260426-1a882efc")));
+        assert_eq!(parse_id_name("Komodo Dragon 3.3"), ("Komodo", None));
+    }
+
+    #[test]
+    fn it_works14() {
+        let mock = MockEngine::new().on("uci", &["id name Stockfish 17.1", "option name Threads type spin default 1 min 1 max 1024", "option name Hash type spin default 16 min 1 max 33554432", "uciok"]);
+        let log = mock.log();
+        let mut engine = mock_engine(mock);
+        assert!(engine.is_stockfish());
+        assert_eq!(engine.version(), Some("17.1"));
+        engine.configure(&EngineConfig { threads: Some(2), hash: Some(128), skill_level: Some(5), ..Default::default() }).unwrap();
+        assert!(matches!(engine.configure(&EngineConfig { threads: Some(0), ..Default::default() }), Err(SFErrors::InvalidOption(_))));
+        assert!(engine.set_option("Hash", OptionValue::Spin(64)).is_ok());
+        let log = log.lock().unwrap();
+        assert!(log.contains(&String::from("setoption name Threads value 2")) && log.contains(&String::from("setoption name Hash value 128")));
+        assert!(!log.iter().any(|l| l.contains("Skill Level")));
+    }
+
+    #[test]
+    fn it_works15() {   // pondering on the expected reply, once it is played and once another move
+        let mock = MockEngine::new()
+            .on("go", &["info depth 15 score cp 20 pv e2e4 e7e5 g1f3", "bestmove e2e4 ponder e7e5"])
+            .on("go", &[])
+            .on("ponderhit", &["info depth 16 score cp 25 pv g1f3 b8c6", "bestmove g1f3 ponder b8c6"])
+            .on("go", &[])
+            .on("stop", &["bestmove f1c4"])
+            .on("go", &["bestmove d2d4"]);
+        let log = mock.log();
+        let mut engine = mock_engine(mock);
+        let limits = SearchLimits::clock(60000, 60000, 0, 0);
+        let fen = "rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 0 1";
+        let fen2 = "rnbqkbnr/pppppppp/8/8/4P3/8/PPPP1PPP/RNBQKBNR b KQkq - 0 1";
+        let res = engine.search(fen, None, &limits).unwrap();
+        assert_eq!((res.bestmove.as_deref(), res.ponder.as_deref()), (Some("e2e4"), Some("e7e5")));
+        engine.go_ponder(fen2, &[], "e7e5", None, &limits).unwrap();
+        assert!(engine.is_pondering());
+        assert!(matches!(engine.wait(), Err(SFErrors::Pondering)));
+        engine.ponderhit().unwrap();
+        assert_eq!(engine.wait().unwrap().bestmove.as_deref(), Some("g1f3"));
+        assert!(matches!(engine.ponderhit(), Err(SFErrors::NotPondering)));
+        engine.go_ponder(fen2, &[], "e7e5", None, &limits).unwrap();
+        assert_eq!(engine.stop().unwrap().bestmove.as_deref(), Some("f1c4"));
+        assert_eq!(engine.get_move_with("rnbqkbnr/pppp1ppp/8/4p3/4P3/8/PPPP1PPP/RNBQKBNR w KQkq - 0 2", None, &limits).unwrap(), SFResults::Normal(String::from("d2d4")));
+        let log = log.lock().unwrap();
+        assert_eq!(log.iter().filter(|l| l.as_str() == "position fen rnbqkbnr/pppppppp/8/8/4P3/8/PPPP1PPP/RNBQKBNR b KQkq - 0 1 moves e7e5").count(), 2);
+        assert_eq!(log.iter().filter(|l| l.as_str() == "go ponder wtime 60000 btime 60000 winc 0 binc 0").count(), 2);
+    }
+}
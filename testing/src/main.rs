@@ -6,6 +6,12 @@ use mainp::Game;
 
 
 fn main() {
+    // cargo run --release -- perft <depth> [fen] or divide <depth> [fen], without arguments the scratch code below runs
+    let args: Vec<String> = std::env::args().collect();
+    if args.len() > 2 && (args[1] == "perft" || args[1] == "divide") {
+        perft_cli(&args[1], &args[2], &args[3..].join(" "));
+        return
+    };
     /*let mut m1 = motor::Mtr::new(true, 5, 6, 13).unwrap();
     m1.enable_motor();
     m1.move_steps(1600, true, 2.5).unwrap();
@@ -49,27 +55,27 @@ fn main() {
         println!("{:?}", i)
     }
     println!("h");
-    let res = a.update(((5, 7), (3, 5)), "e3c5", 2000, 500);
+    let res = a.update(((5, 7), (3, 5)), "e3c5");
     for i in a.fields {
         println!("{:?}", i)
     };
     println!("{:?}", res);
-    let res = a.update(((0, 7), (0, 9)), "e8g8", 2000, 500).unwrap();
+    let res = a.update(((0, 7), (0, 9)), "e8g8").unwrap();
     for i in a.fields {
         println!("{:?}", i)
     };
     println!("{:?}", res);
-    let res = a.update(((4, 9), (3, 9)), "g4g5", 2000, 500).unwrap();
+    let res = a.update(((4, 9), (3, 9)), "g4g5").unwrap();
     for i in a.fields {
         println!("{:?}", i)
     };
     println!("{:?}", res);
-    let res = a.update(((1, 10), (3, 10)), "h7h5", 2000, 500).unwrap();
+    let res = a.update(((1, 10), (3, 10)), "h7h5").unwrap();
     for i in a.fields {
         println!("{:?}", i)
     };
     println!("{:?}", res);
-    let res = a.update(((3, 9), (2, 10)), "g5h6", 2000, 500).unwrap();
+    let res = a.update(((3, 9), (2, 10)), "g5h6").unwrap();
     for i in a.fields {
         println!("{:?}", i)
     };
@@ -151,3 +157,30 @@ fn main() {
     BitList::from_pos(&Position::from_fen("rnbqkbnr/ppp1pppp/8/3p4/4P3/8/PPPP1PPP/RNBQKBNR w KQkq d6 0 1").unwrap()).print_out();
     println!("finished");
 }
+
+fn perft_cli(mode: &str, depth: &str, fen: &str) {
+    let depth: u32 = match depth.parse() {
+        Ok(d) => d,
+        Err(rr) => return println!("invalid depth {}: {:?}", depth, rr)
+    };
+    let pos = if fen.is_empty() {
+        Position::new_reset()
+    } else {
+        match Position::from_fen(fen) {
+            Ok(p) => p,
+            Err(rr) => return println!("invalid fen: {:?}", rr)
+        }
+    };
+    let start = std::time::Instant::now();
+    let nodes = if mode == "divide" {
+        let res = pos.divide(depth);
+        for (mv, n) in &res {
+            println!("{}: {}", mv, n);
+        };
+        res.iter().map(|(_, n)| n).sum()
+    } else {
+        pos.perft(depth)
+    };
+    let secs = start.elapsed().as_secs_f64();
+    println!("nodes: {}, time: {:.3}s, nps: {:.0}", nodes, secs, nodes as f64 / secs.max(1e-9));
+}